@@ -0,0 +1,188 @@
+//! Allocation rules: splitting income across piles the same way every time.
+//!
+//! Rules are stored in the budget by name. Allocating an amount stages an `Add` transaction on
+//! each target pile, leaving them to be committed like any other transaction.
+
+use serde::Serialize;
+
+use crate::{
+    budget_management::update_budget,
+    error::{BudgeyError, BudgeyResult},
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    models::{
+        allocation::{Allocation, AllocationRule, AllocationTarget},
+        budget::Budget,
+        pile::Pile,
+        record_transaction::{Transaction, TransactionType},
+    },
+    operations, BudgeyContext,
+};
+
+/// The rule used when none is named and the budget has several.
+pub const DEFAULT_RULE_NAME: &str = "default";
+
+/// An amount split by a named rule, ready to be staged.
+#[derive(Debug, Clone, Serialize)]
+pub struct AllocationPlan {
+    pub rule_name: String,
+    pub amount: f32,
+    #[serde(flatten)]
+    pub allocation: Allocation,
+}
+
+/// Adds a rule to the focused budget, or replaces the rule with the same name.
+///
+/// Targets are given as <pile>=<share> and minimums as <pile>=<amount>. Every pile must exist.
+pub fn set_rule(
+    context: &BudgeyContext,
+    rule_name: &str,
+    targets: &[String],
+    minimums: &[String],
+    budget_io: &impl BudgetIO,
+) -> BudgeyResult<AllocationRule> {
+    let budget = budget_io.get_current_budget(context)?;
+    let mut targets = targets
+        .iter()
+        .map(|target| AllocationTarget::parse(target))
+        .collect::<BudgeyResult<Vec<_>>>()?;
+    for minimum in minimums {
+        let (pile_name, amount) = minimum
+            .split_once('=')
+            .and_then(|(pile_name, amount)| {
+                Some((
+                    pile_name,
+                    amount
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|amount| amount.is_finite())?,
+                ))
+            })
+            .ok_or_else(|| {
+                BudgeyError::InvalidAllocationRule(format!(
+                    "{:?} should be <pile>=<amount>",
+                    minimum
+                ))
+            })?;
+        let target = targets
+            .iter_mut()
+            .find(|target| target.pile_name == pile_name)
+            .ok_or_else(|| {
+                BudgeyError::InvalidAllocationRule(format!(
+                    "{} has a minimum but isn't a target",
+                    pile_name
+                ))
+            })?;
+        *target = target.clone().with_minimum(Some(amount));
+    }
+    if let Some(target) = targets
+        .iter()
+        .find(|target| !budget.pile_names.contains(&target.pile_name))
+    {
+        return Err(BudgeyError::PileNotFound(target.pile_name.to_string()));
+    }
+    let rule = AllocationRule::new(targets)?;
+    update_budget(
+        &context.get_current_budget_path(),
+        &budget.set_allocation_rule(rule_name, &rule),
+    )?;
+    Ok(rule)
+}
+
+/// Removes a rule from the focused budget.
+pub fn remove_rule(
+    context: &BudgeyContext,
+    rule_name: &str,
+    budget_io: &impl BudgetIO,
+) -> BudgeyResult<()> {
+    let budget = budget_io.get_current_budget(context)?;
+    if !budget.allocation_rules.contains_key(rule_name) {
+        return Err(BudgeyError::AllocationRuleNotFound(rule_name.to_string()));
+    }
+    update_budget(
+        &context.get_current_budget_path(),
+        &budget.remove_allocation_rule(rule_name),
+    )?;
+    Ok(())
+}
+
+/// Works out how the named rule splits an amount, without staging anything.
+///
+/// If no rule is named, `default` is used, or the only rule if there is just one.
+pub fn plan_allocation(
+    context: &BudgeyContext,
+    amount: f32,
+    rule_name: Option<&str>,
+    budget_io: &impl BudgetIO,
+) -> BudgeyResult<AllocationPlan> {
+    if amount <= 0.0 {
+        return Err(BudgeyError::CannotAllocate(format!(
+            "{} isn't a positive amount",
+            amount
+        )));
+    }
+    let budget = budget_io.get_current_budget(context)?;
+    let (rule_name, rule) = resolve_rule(&budget, rule_name)?;
+    Ok(AllocationPlan {
+        rule_name: rule_name.to_string(),
+        amount,
+        allocation: rule.allocate(amount)?,
+    })
+}
+
+/// Stages an `Add` transaction for each part of the plan on its pile. Nothing is staged unless
+/// every part can be.
+pub fn stage_allocation(
+    context: &BudgeyContext,
+    plan: &AllocationPlan,
+    note: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<(Pile, Transaction)>> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let default_note = format!("Allocated by rule {}", plan.rule_name);
+    let mut staged = vec![];
+    for part in &plan.allocation.parts {
+        let pile = pile_io
+            .maybe_get_pile(&part.pile_name, &current_budget)?
+            .ok_or_else(|| BudgeyError::PileNotFound(part.pile_name.to_string()))?;
+        let transaction = Transaction::new(
+            TransactionType::Add,
+            part.amount,
+            Some(note.unwrap_or(&default_note)),
+        );
+        staged.push((
+            operations::stage_with_hook(context, pile, &transaction, false)?,
+            transaction,
+        ));
+    }
+    for (pile, _) in &staged {
+        pile_io.update_pile(pile)?;
+    }
+    Ok(staged)
+}
+
+fn resolve_rule<'a>(
+    budget: &'a Budget,
+    rule_name: Option<&'a str>,
+) -> BudgeyResult<(&'a str, &'a AllocationRule)> {
+    if let Some(name) = rule_name {
+        return budget
+            .allocation_rules
+            .get(name)
+            .map(|rule| (name, rule))
+            .ok_or_else(|| BudgeyError::AllocationRuleNotFound(name.to_string()));
+    }
+    if let Some(rule) = budget.allocation_rules.get(DEFAULT_RULE_NAME) {
+        return Ok((DEFAULT_RULE_NAME, rule));
+    }
+    match budget
+        .allocation_rules
+        .iter()
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [(name, rule)] => Ok((name.as_str(), rule)),
+        _ => Err(BudgeyError::NoAllocationRule),
+    }
+}
@@ -2,6 +2,7 @@ use std::fs;
 
 use crate::{
     budgey_state::write_budgey_state,
+    encryption,
     models::budget::Budget,
     utils::{concat_paths, create_json_path},
     BudgeyContext,
@@ -9,7 +10,11 @@ use crate::{
 
 pub fn update_budget(budget_path: &str, budget: &Budget) -> anyhow::Result<()> {
     let budget_file_path = create_json_path(budget_path, &budget.budget_detail.budget_name);
-    fs::write(budget_file_path, serde_json::to_string(&budget)?)?;
+    encryption::write_file(
+        budget_path,
+        &budget_file_path,
+        &serde_json::to_string(&budget)?,
+    )?;
     Ok(())
 }
 pub fn delete_budget(budgey_context: &BudgeyContext, budget_name: &str) -> anyhow::Result<()> {
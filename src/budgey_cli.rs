@@ -1,8 +1,21 @@
-use clap::{Parser, Subcommand};
+use budgey::models::policy::{OverdraftPolicy, SpendingPeriod};
+use clap::{Args, Parser, Subcommand};
+
+use crate::output::OutputFormat;
 
 /// A finance tracking and budgeting tool
 #[derive(Debug, Parser)]
-pub enum BudgeyCLI {
+pub struct BudgeyCLI {
+    /// The format to write output in.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: BudgeyCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BudgeyCommand {
     /// Initialises Budgey
     #[command(name = "init", arg_required_else_help = true)]
     Init {
@@ -10,12 +23,125 @@ pub enum BudgeyCLI {
         name: String,
     },
 
+    /// Copy a budget from another budgey folder, saving it as the `origin` remote.
+    #[command(name = "clone", arg_required_else_help = true)]
+    Clone {
+        /// The path of the budgey folder to clone from.
+        path: String,
+        /// The budget to clone. Only needed if the folder has several budgets.
+        #[arg(long)]
+        budget: Option<String>,
+    },
+
+    /// Pack a budget into a single file, or restore one from it.
+    #[command(name = "bundle", arg_required_else_help = true)]
+    Bundle {
+        #[command(subcommand)]
+        subcommand: BundleSubcommand,
+    },
+
+    /// Display the journal of commands that changed budgey's files, newest first.
+    #[command(name = "reflog")]
+    Reflog {
+        /// Show at most this many entries.
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+
+    /// Roll back the last commands that changed budgey's files, restoring them from the
+    /// journal.
+    #[command(name = "undo")]
+    Undo {
+        /// The number of commands to roll back.
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+
     #[command(flatten)]
     Subcommands(Commands),
 }
 
+impl BudgeyCommand {
+    /// Whether the command reads or changes the configuration, so it can run when the config
+    /// files are invalid.
+    pub fn is_config(&self) -> bool {
+        matches!(self, BudgeyCommand::Subcommands(Commands::Config { .. }))
+    }
+
+    /// Whether the command can change budgey's files, which are then written to the undo
+    /// journal.
+    pub fn is_journaled(&self) -> bool {
+        match self {
+            BudgeyCommand::Init { .. } | BudgeyCommand::Clone { .. } => true,
+            BudgeyCommand::Bundle { subcommand } => {
+                matches!(subcommand, BundleSubcommand::Import { .. })
+            }
+            BudgeyCommand::Reflog { .. } | BudgeyCommand::Undo { .. } => false,
+            BudgeyCommand::Subcommands(command) => command.changes_files(),
+        }
+    }
+
+    /// The budget the command works on other than the focused one, whose files must be
+    /// journaled before it runs. Budgets the command creates are found afterwards.
+    pub fn named_budget(&self) -> Option<&str> {
+        match self {
+            BudgeyCommand::Init { name }
+            | BudgeyCommand::Subcommands(Commands::Budget {
+                subcommand: Some(BudgetSubcommand::Delete { name }),
+            }) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl Commands {
+    /// Whether the command can change the local budgey folder. Pushing only changes the remote.
+    fn changes_files(&self) -> bool {
+        match self {
+            Commands::Status
+            | Commands::Log { .. }
+            | Commands::Show { .. }
+            | Commands::Overview { .. }
+            | Commands::Push { .. }
+            | Commands::Unlock { .. }
+            | Commands::Lock
+            | Commands::Chain
+            | Commands::Diff { .. } => false,
+            Commands::Budget { subcommand } => {
+                !matches!(subcommand, None | Some(BudgetSubcommand::List))
+            }
+            Commands::Remote { subcommand } => subcommand.is_some(),
+            Commands::Config { subcommand } => matches!(
+                subcommand,
+                ConfigSubcommand::Set { .. } | ConfigSubcommand::Unset { .. }
+            ),
+            Commands::Pile { subcommand } => !matches!(
+                subcommand,
+                None | Some(PileSubcommand::List | PileSubcommand::Balance { .. })
+            ),
+            Commands::Tag {
+                name, subcommand, ..
+            } => name.is_some() || matches!(subcommand, Some(TagSubcommand::Delete { .. })),
+            Commands::Goal { subcommand } => {
+                !matches!(subcommand, None | Some(GoalSubcommand::List))
+            }
+            Commands::Allocation { subcommand } => subcommand.is_some(),
+            Commands::Tui
+            | Commands::Pull { .. }
+            | Commands::Encrypt
+            | Commands::Decrypt
+            | Commands::Restore
+            | Commands::Commit { .. }
+            | Commands::CherryPick { .. }
+            | Commands::Allocate { .. }
+            | Commands::Add { .. }
+            | Commands::Withdraw { .. } => true,
+        }
+    }
+}
+
 /// Commands for the Budgey CLI
-#[derive(Debug, Clone, Parser)]
+#[derive(Debug, Clone, Subcommand)]
 pub enum Commands {
     /// Create, manage and switch between budgets.
     Budget {
@@ -23,14 +149,89 @@ pub enum Commands {
         subcommand: Option<BudgetSubcommand>,
     },
 
+    /// Display the focused budget and pile, the last record and any staged transactions.
+    #[command(name = "status")]
+    Status,
+
     /// Display the record log for the current pile.  
     #[command(name = "log")]
-    Log,
+    Log {
+        #[command(flatten)]
+        options: LogOptions,
+    },
+
+    /// Display a record of the current pile and every transaction in it.
+    #[command(name = "show", arg_required_else_help = true)]
+    Show {
+        /// The id of the record to show. Any unique prefix of the id is accepted.
+        record_id: String,
+    },
+
+    /// Display the balance of every pile in the current budget.
+    #[command(name = "overview")]
+    Overview {
+        /// Show the balances as of the end of a date (YYYY-MM-DD).
+        #[arg(long)]
+        at: Option<String>,
+    },
+
+    /// Open a full-screen terminal UI to browse piles and records and stage, commit and restore
+    /// transactions.
+    #[command(name = "tui")]
+    Tui,
+
+    /// Manage the remotes of the focused budget. Lists them if no subcommand is given.
+    #[command(name = "remote")]
+    Remote {
+        #[command(subcommand)]
+        subcommand: Option<RemoteSubcommand>,
+    },
+
+    /// Send the focused budget's records to a remote, fast-forwarding piles that are behind.
+    #[command(name = "push")]
+    Push {
+        /// The remote to push to. Defaults to `origin`, or the only remote.
+        remote: Option<String>,
+    },
+
+    /// Take a remote's records into the focused budget, fast-forwarding piles that are behind.
+    #[command(name = "pull")]
+    Pull {
+        /// The remote to pull from. Defaults to `origin`, or the only remote.
+        remote: Option<String>,
+    },
+
+    /// Encrypt the focused budget's files with a passphrase.
+    #[command(name = "encrypt")]
+    Encrypt,
+
+    /// Write the focused budget's files back unencrypted.
+    #[command(name = "decrypt")]
+    Decrypt,
+
+    /// Enter the focused budget's passphrase once for the following commands.
+    #[command(name = "unlock")]
+    Unlock {
+        /// How long to keep the budget unlocked, in minutes.
+        #[arg(long, default_value_t = 15)]
+        minutes: u64,
+    },
+
+    /// Forget the focused budget's passphrase before the unlock runs out.
+    #[command(name = "lock")]
+    Lock,
 
     /// Display the current transaction chain for the current pile.
     #[command(name = "chain")]
     Chain,
 
+    /// Get and set configuration options for all budgets or the focused budget.
+    #[command(name = "config", arg_required_else_help = true)]
+    Config {
+        #[command(subcommand)]
+        subcommand: ConfigSubcommand,
+    },
+
     /// Create, manage and switch piles in the currently focused budget.
     #[command(name = "pile")]
     Pile {
@@ -49,36 +250,215 @@ pub enum Commands {
     /// record history.
     ///
     /// This can only be undone with a revert.
-    #[command(name = "commit", arg_required_else_help = true)]
+    #[command(name = "commit")]
     Commit {
-        /// A required message for the transaction commit.
+        /// The message for the transaction commit.
         ///
-        /// This message should be used to describe the transactions made.
-        #[arg(short, long, required = true)]
-        message: String,
+        /// This message should be used to describe the transactions made. Required unless
+        /// `commit.template` is set.
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Commit even if the pile's policy refuses the staged transactions.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Copies a record's transactions from another pile onto the focused pile as a new record.
+    #[command(name = "cherry-pick", arg_required_else_help = true)]
+    CherryPick {
+        /// The id of the record to cherry-pick.
+        record_id: String,
+
+        /// The name of the pile to cherry-pick the record from.
+        /// If not provided, every other pile in the current budget will be searched.
+        #[arg(short, long)]
+        from: Option<String>,
+    },
+
+    /// Compare two piles, or the records between two records of the focused pile.
+    #[command(name = "diff", arg_required_else_help = true)]
+    Diff {
+        /// The first pile name, or a record range in the form <record>..<record>.
+        first: String,
+
+        /// The second pile name. Omit when comparing a record range.
+        second: Option<String>,
+    },
+
+    /// Name records of the current budget, such as month-end checkpoints.
+    ///
+    /// Tags can be used anywhere a record id is expected.
+    #[command(name = "tag", args_conflicts_with_subcommands = true)]
+    Tag {
+        /// The name of the new tag.
+        name: Option<String>,
+
+        /// The id of the record to tag.
+        /// If not provided, the last record of the focused pile is tagged.
+        record_id: Option<String>,
+
+        #[command(subcommand)]
+        subcommand: Option<TagSubcommand>,
+    },
+
+    /// Set savings goals for piles and track progress towards them. Lists them if no subcommand
+    /// is given.
+    #[command(name = "goal")]
+    Goal {
+        #[command(subcommand)]
+        subcommand: Option<GoalSubcommand>,
+    },
+
+    /// Manage the rules `allocate` splits income by. Lists them if no subcommand is given.
+    #[command(name = "allocation")]
+    Allocation {
+        #[command(subcommand)]
+        subcommand: Option<AllocationSubcommand>,
+    },
+
+    /// Split an amount across piles by an allocation rule, staging an 'add' transaction on each.
+    ///
+    /// Shows how the amount will be split and asks before staging.
+    #[command(name = "allocate", arg_required_else_help = true)]
+    Allocate {
+        #[command(flatten)]
+        args: AllocateArgs,
     },
 
     /// Create an 'add' transaction, where money is added to the current pile.
     #[command(name = "add", arg_required_else_help = true)]
     Add {
-        /// The amount of the transaction. Supports expressions.
-        amount: String,
-
-        /// An optional note for the transaction.
-        #[arg(short, long)]
-        note: Option<String>,
+        #[command(flatten)]
+        transaction: TransactionArgs,
     },
     /// Create an 'withdraw' transaction, where money is withdrawn from the pile.
     #[command(name = "withdraw", arg_required_else_help = true)]
     Withdraw {
-        /// The amount of the transaction. Supports expressions.
-        amount: String,
-
-        /// An optional note for the transaction.
-        #[arg(short, long)]
-        note: Option<String>,
+        #[command(flatten)]
+        transaction: TransactionArgs,
     },
 }
+/// The details of a transaction to stage.
+#[derive(Debug, Clone, Args)]
+pub struct TransactionArgs {
+    /// The amount of the transaction. Supports expressions.
+    pub amount: String,
+
+    /// An optional note for the transaction.
+    #[arg(short, long)]
+    pub note: Option<String>,
+
+    /// The day the money moved, if not today: YYYY-MM-DD, yesterday or <n> days ago.
+    #[arg(long)]
+    pub date: Option<String>,
+
+    /// Split the amount across piles, staging a linked part on each. Repeat for every part, as
+    /// <pile>=<amount>. One part can be <pile>=rest.
+    #[arg(long = "split", value_name = "PILE=AMOUNT")]
+    pub splits: Vec<String>,
+
+    /// Stage the transaction even if the pile's policy refuses it.
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// The amount to allocate and how.
+#[derive(Debug, Clone, Args)]
+pub struct AllocateArgs {
+    /// The amount to allocate. Supports expressions.
+    pub amount: String,
+
+    /// The rule to allocate by. Defaults to `default`, or the only rule.
+    #[arg(long)]
+    pub rule: Option<String>,
+
+    /// An optional note for the transactions.
+    #[arg(short, long)]
+    pub note: Option<String>,
+
+    /// Only show how the amount would be split.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Stage the transactions without asking.
+    #[arg(short, long, conflicts_with = "dry_run")]
+    pub yes: bool,
+}
+
+/// Changes to a pile's policy. Settings that aren't given are kept.
+#[derive(Debug, Clone, Args)]
+pub struct PolicyArgs {
+    /// Whether withdrawals may take the balance below zero: allow, warn or forbid.
+    #[arg(long)]
+    pub overdraft: Option<OverdraftPolicy>,
+
+    /// The most a single withdrawal can take.
+    #[arg(long, conflicts_with = "no_max_withdrawal")]
+    pub max_withdrawal: Option<f32>,
+
+    /// Remove the limit per withdrawal.
+    #[arg(long)]
+    pub no_max_withdrawal: bool,
+
+    /// The most that can be withdrawn in each period. Requires --per.
+    #[arg(long, requires = "per", conflicts_with = "no_cap")]
+    pub cap: Option<f32>,
+
+    /// The period the cap applies to: day, week or month.
+    #[arg(long, requires = "cap")]
+    pub per: Option<SpendingPeriod>,
+
+    /// Remove the spending cap.
+    #[arg(long)]
+    pub no_cap: bool,
+}
+
+/// Options for filtering and formatting the record log.
+#[derive(Debug, Clone, Args)]
+pub struct LogOptions {
+    /// Only show records made on or after this date (YYYY-MM-DD, yesterday or <n> days ago).
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only show records made on or before this date (YYYY-MM-DD, yesterday or <n> days ago).
+    #[arg(long)]
+    pub until: Option<String>,
+
+    /// Only show records whose message contains this text. Case insensitive.
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// Only show records whose author's name or email contains this text. Case insensitive.
+    #[arg(long)]
+    pub author: Option<String>,
+
+    /// Limit the number of records shown.
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+
+    /// Show each record on a single line.
+    #[arg(long)]
+    pub oneline: bool,
+
+    /// Show the transactions in each record.
+    #[arg(short = 'p', long = "patch")]
+    pub show_transactions: bool,
+
+    /// The name of the pile to show the log of.
+    /// If not provided, the log of the currently focused pile is shown.
+    #[arg(long)]
+    pub pile: Option<String>,
+
+    /// Draw the record history as a graph, showing where piles forked.
+    #[arg(long, conflicts_with_all = ["since", "until", "grep", "author", "show_transactions"])]
+    pub graph: bool,
+
+    /// Show every pile in the current budget. Requires --graph.
+    #[arg(long, requires = "graph", conflicts_with = "pile")]
+    pub all: bool,
+}
+
 #[derive(Debug, Subcommand, Clone)]
 pub enum BudgetSubcommand {
     /// Set the named working budget.
@@ -101,6 +481,165 @@ pub enum BudgetSubcommand {
     Delete { name: String },
 }
 
+#[derive(Debug, Subcommand, Clone)]
+pub enum BundleSubcommand {
+    /// Write a budget, its piles and its settings to a bundle file with a checksummed manifest.
+    #[command(name = "create", arg_required_else_help = true)]
+    Create {
+        /// The budget to bundle.
+        budget: String,
+        /// The path of the bundle file to write.
+        file: String,
+    },
+    /// Restore a budget from a bundle file and focus it. Corrupt bundles are refused.
+    #[command(name = "import", arg_required_else_help = true)]
+    Import {
+        /// The path of the bundle file to read.
+        file: String,
+        /// Import the budget under this name instead of the one it was bundled with.
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum ConfigSubcommand {
+    /// Display the value of a key. Without a scope, shows the value that applies to the focused
+    /// budget.
+    #[command(name = "get", arg_required_else_help = true)]
+    Get {
+        /// The key to get, such as `user.name`.
+        key: String,
+        #[command(flatten)]
+        scope: ConfigScope,
+    },
+
+    /// Set the value of a key. Writes to the focused budget's config unless `--global` is given.
+    #[command(name = "set", arg_required_else_help = true)]
+    Set {
+        /// The key to set, such as `user.name`.
+        key: String,
+        /// The value to set. `true` and `false` are booleans, anything else is text.
+        value: String,
+        /// Set the key for every budget.
+        #[arg(long)]
+        global: bool,
+    },
+
+    /// Remove a key. Removes from the focused budget's config unless `--global` is given.
+    #[command(name = "unset", arg_required_else_help = true)]
+    Unset {
+        /// The key to remove.
+        key: String,
+        /// Remove the key from the global config.
+        #[arg(long)]
+        global: bool,
+    },
+
+    /// List every setting. Without a scope, lists the settings that apply to the focused budget,
+    /// including defaults.
+    #[command(name = "list")]
+    List {
+        #[command(flatten)]
+        scope: ConfigScope,
+    },
+}
+
+/// Which config file to read from.
+#[derive(Debug, Args, Clone)]
+pub struct ConfigScope {
+    /// Only read the global config.
+    #[arg(long, conflicts_with = "budget")]
+    pub global: bool,
+    /// Only read the focused budget's config.
+    #[arg(long)]
+    pub budget: bool,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum RemoteSubcommand {
+    /// Add a remote.
+    #[command(name = "add", arg_required_else_help = true)]
+    Add {
+        /// The name of the remote, such as `home`.
+        name: String,
+        /// The path of the budgey folder, such as a folder on a shared drive.
+        path: String,
+    },
+
+    /// Remove a remote. Nothing is deleted from the remote itself.
+    #[command(name = "remove", arg_required_else_help = true)]
+    Remove {
+        /// The name of the remote to remove.
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum GoalSubcommand {
+    /// Set the amount a pile is being saved up to and the date to reach it by.
+    #[command(name = "set", arg_required_else_help = true)]
+    Set {
+        /// The name of the pile.
+        pile: String,
+        /// The amount to save up to. Supports expressions.
+        amount: String,
+        /// The date to reach the amount by (YYYY-MM-DD).
+        #[arg(long)]
+        by: String,
+    },
+
+    /// List every goal with its progress, the monthly saving needed and whether recent inflow is
+    /// on pace.
+    #[command(name = "ls")]
+    List,
+
+    /// Remove the goal of a pile.
+    #[command(name = "remove", arg_required_else_help = true)]
+    Remove {
+        /// The name of the pile.
+        pile: String,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum AllocationSubcommand {
+    /// Add an allocation rule, or replace the rule with the same name.
+    #[command(name = "set", arg_required_else_help = true)]
+    Set {
+        /// The name of the rule, such as `payday`.
+        name: String,
+        /// How much each pile gets: a percentage such as bills=50%, a fixed amount such as
+        /// holiday=200, or rest for whatever is left.
+        #[arg(required = true, value_name = "PILE=SHARE")]
+        targets: Vec<String>,
+        /// The least a pile gets, taken from what would otherwise be left. Repeat for every pile.
+        #[arg(long = "minimum", value_name = "PILE=AMOUNT")]
+        minimums: Vec<String>,
+    },
+
+    /// Remove an allocation rule.
+    #[command(name = "remove", arg_required_else_help = true)]
+    Remove {
+        /// The name of the rule to remove.
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum TagSubcommand {
+    /// List all tags in the current budget.
+    #[command(name = "ls")]
+    List,
+
+    /// Delete a tag.
+    #[command(name = "delete", arg_required_else_help = true)]
+    Delete {
+        /// The name of the tag to delete.
+        name: String,
+    },
+}
+
 #[derive(Debug, Subcommand, Clone)]
 pub enum PileSubcommand {
     /// Creates a new pile.
@@ -133,6 +672,10 @@ pub enum PileSubcommand {
     Balance {
         /// The name of the pile to check the balance of.
         name: Option<String>,
+
+        /// Show the balance as of a record or tag, or the end of a date (YYYY-MM-DD).
+        #[arg(long)]
+        at: Option<String>,
     },
 
     /// Focus a new pile.
@@ -141,6 +684,25 @@ pub enum PileSubcommand {
         /// The name of the pile to focus on.
         name: String,
     },
+
+    /// Show or change the limits a pile's withdrawals are held to. `withdraw` and `commit` refuse
+    /// transactions that break them unless given --force.
+    #[command(name = "policy")]
+    Policy {
+        /// The name of the pile.
+        /// If not provided, the focused pile's policy is used.
+        name: Option<String>,
+
+        #[command(flatten)]
+        changes: PolicyArgs,
+    },
+
+    /// Replays the focused pile's own records on top of another pile's latest history.
+    #[command(name = "rebase", arg_required_else_help = true)]
+    Rebase {
+        /// The name of the pile to rebase onto.
+        onto: String,
+    },
     // TODO:
     // /// Reverts a transaction commit.
     // #[command(name = "revert", arg_required_else_help = true)]
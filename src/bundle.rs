@@ -0,0 +1,396 @@
+//! Bundles: a budget packed into one file to back it up or move it to another machine.
+//!
+//! A bundle is a gzipped tar archive holding a manifest and every file in the budget's folder,
+//! including its piles and configuration. The manifest lists each file with its size and SHA-256
+//! checksum, and a bundle is only imported if every file matches it.
+//!
+//! Hooks are left out, like Git leaves them out of clones, so importing a bundle never installs
+//! programs that budgey would run.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Read,
+    path::{Component, Path},
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    budget_management::update_budget,
+    budgey_state::{write_budgey_state, BudgeyState},
+    encryption,
+    error::{BudgeyError, BudgeyResult},
+    hooks::HOOKS_FOLDER_NAME,
+    models::budget::Budget,
+    utils::{self, concat_paths, create_json_file_name, create_json_path},
+    BudgeyConfig,
+};
+
+/// The version of the bundle layout written by this version of budgey.
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "manifest.json";
+
+/// The folder in the archive that holds the budget's files.
+const BUDGET_FOLDER: &str = "budget";
+
+/// Describes what a bundle holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub budget_name: String,
+    #[serde(deserialize_with = "utils::deserialize_time_stamp")]
+    pub time_stamp: DateTime<Utc>,
+    pub files: Vec<BundledFile>,
+}
+
+/// A file in a bundle, with its path relative to the budget's folder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Packs a budget's folder into a bundle at the given path, returning its manifest.
+pub fn create_bundle(
+    config: &BudgeyConfig,
+    budget_name: &str,
+    bundle_path: &str,
+) -> BudgeyResult<BundleManifest> {
+    let budget_path = config.get_budget_path(budget_name);
+    if !Path::new(&create_json_path(&budget_path, budget_name)).exists() {
+        return Err(BudgeyError::BudgetNotFound(budget_name.to_string()));
+    }
+    let mut files = utils::read_files_recursively(Path::new(&budget_path))?;
+    files.retain(|path, _| !is_hook(path));
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        budget_name: budget_name.to_string(),
+        time_stamp: Utc::now(),
+        files: files
+            .iter()
+            .map(|(path, contents)| bundled_file(path, contents))
+            .collect(),
+    };
+
+    let encoder = GzEncoder::new(fs::File::create(bundle_path)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    append_file(
+        &mut archive,
+        MANIFEST_PATH,
+        &serde_json::to_vec_pretty(&manifest).map_err(anyhow::Error::from)?,
+    )?;
+    for (path, contents) in &files {
+        append_file(&mut archive, &concat_paths(BUDGET_FOLDER, path), contents)?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(manifest)
+}
+
+/// Restores a bundle as a new budget and focuses it, returning the bundle's manifest.
+///
+/// The budget keeps the name it was bundled with unless another is given. Nothing is written if
+/// the bundle doesn't match its manifest or a budget with the name already exists.
+pub fn import_bundle(
+    config: &BudgeyConfig,
+    local_state: Option<&BudgeyState>,
+    bundle_path: &str,
+    budget_name: Option<&str>,
+) -> BudgeyResult<BundleManifest> {
+    let mut files = read_archive(fs::File::open(bundle_path)?)?;
+    let manifest = files
+        .remove(MANIFEST_PATH)
+        .ok_or_else(|| BudgeyError::CorruptBundle("it has no manifest".to_string()))?;
+    let manifest = serde_json::from_slice::<BundleManifest>(&manifest)
+        .map_err(|e| BudgeyError::CorruptBundle(format!("the manifest is invalid: {}", e)))?;
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(BudgeyError::UnsupportedBundleVersion(
+            manifest.format_version,
+        ));
+    }
+    if !is_folder_name(&manifest.budget_name) {
+        return Err(BudgeyError::CorruptBundle(format!(
+            "{:?} isn't a budget name",
+            manifest.budget_name
+        )));
+    }
+    let budget_name = budget_name.unwrap_or(&manifest.budget_name);
+    if !is_folder_name(budget_name) {
+        return Err(BudgeyError::InvalidBudgetName(budget_name.to_string()));
+    }
+
+    let files = files
+        .into_iter()
+        .map(
+            |(path, file)| match path.strip_prefix(&format!("{}/", BUDGET_FOLDER)) {
+                Some(path) => Ok((path.to_string(), file)),
+                None => Err(BudgeyError::CorruptBundle(format!(
+                    "{} isn't listed in the manifest",
+                    path
+                ))),
+            },
+        )
+        .collect::<BudgeyResult<BTreeMap<String, Vec<u8>>>>()?;
+    verify(&manifest, &files).map_err(BudgeyError::CorruptBundle)?;
+
+    let budget_path = config.get_budget_path(budget_name);
+    if local_state.is_some_and(|state| state.budget_names.iter().any(|name| name == budget_name))
+        || Path::new(&budget_path).exists()
+    {
+        return Err(BudgeyError::BudgetAlreadyExists(budget_name.to_string()));
+    }
+    let budget_json_name = create_json_file_name(&manifest.budget_name);
+    if !files.contains_key(&budget_json_name) {
+        return Err(BudgeyError::CorruptBundle(format!(
+            "{} is missing",
+            budget_json_name
+        )));
+    }
+
+    if let Err(e) = write_budget(&budget_path, &files, &manifest.budget_name, budget_name) {
+        // Nothing existed at the path before, so a partly written budget can be removed.
+        if Path::new(&budget_path).exists() {
+            fs::remove_dir_all(&budget_path)?;
+        }
+        return Err(e);
+    }
+
+    let state = match local_state {
+        Some(state) => state
+            .add_budget_name(budget_name)
+            .change_focused_budget_name(budget_name),
+        None => BudgeyState::new_init(budget_name),
+    };
+    write_budgey_state(config, &state)?;
+    Ok(manifest)
+}
+
+fn write_budget(
+    budget_path: &str,
+    files: &BTreeMap<String, Vec<u8>>,
+    bundled_name: &str,
+    budget_name: &str,
+) -> BudgeyResult<()> {
+    fs::create_dir_all(budget_path)?;
+    // Bundles made by earlier versions can hold hooks, which aren't restored.
+    for (path, contents) in files.iter().filter(|(path, _)| !is_hook(path)) {
+        let path = Path::new(budget_path).join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+    }
+    // The budget file is written under the budget's name, and may be encrypted.
+    rename_budget_file(budget_path, bundled_name, budget_name)
+}
+
+fn rename_budget_file(budget_path: &str, old_name: &str, new_name: &str) -> BudgeyResult<()> {
+    let old_path = create_json_path(budget_path, old_name);
+    let budget = encryption::read_file(budget_path, &old_path)?;
+    let budget = serde_json::from_str::<Budget>(&budget)
+        .map_err(|e| BudgeyError::CorruptBundle(format!("{}: {}", old_path, e)))?;
+    if old_name != new_name {
+        update_budget(budget_path, &budget.rename(new_name))?;
+        fs::remove_file(old_path)?;
+    }
+    Ok(())
+}
+
+/// Checks that the files are exactly the ones the manifest lists, describing the first
+/// difference found.
+fn verify(manifest: &BundleManifest, files: &BTreeMap<String, Vec<u8>>) -> Result<(), String> {
+    let mut listed = BTreeSet::new();
+    for expected in &manifest.files {
+        let is_relative = Path::new(&expected.path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !is_relative {
+            return Err(format!("{} is outside the budget's folder", expected.path));
+        }
+        let Some(contents) = files.get(&expected.path) else {
+            return Err(format!("{} is missing", expected.path));
+        };
+        if bundled_file(&expected.path, contents) != *expected {
+            return Err(format!("{} doesn't match its checksum", expected.path));
+        }
+        listed.insert(&expected.path);
+    }
+    match files.keys().find(|path| !listed.contains(path)) {
+        Some(path) => Err(format!("{} isn't listed in the manifest", path)),
+        None => Ok(()),
+    }
+}
+
+/// Whether the name is a single folder name, so a budget with it stays in the budgey folder.
+fn is_folder_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// Whether a path relative to the budget's folder is in its hooks folder.
+fn is_hook(path: &str) -> bool {
+    Path::new(path).starts_with(HOOKS_FOLDER_NAME)
+}
+
+fn bundled_file(path: &str, contents: &[u8]) -> BundledFile {
+    BundledFile {
+        path: path.to_string(),
+        size: contents.len() as u64,
+        sha256: hex::encode(Sha256::digest(contents)),
+    }
+}
+
+fn append_file(
+    archive: &mut tar::Builder<impl std::io::Write>,
+    path: &str,
+    contents: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, path, contents)
+}
+
+fn read_archive(file: fs::File) -> BudgeyResult<BTreeMap<String, Vec<u8>>> {
+    let corrupt = |e: std::io::Error| BudgeyError::CorruptBundle(e.to_string());
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut files = BTreeMap::new();
+    for entry in archive.entries().map_err(corrupt)? {
+        let mut entry = entry.map_err(corrupt)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(corrupt)?.to_string_lossy().to_string();
+        let mut contents = vec![];
+        entry.read_to_end(&mut contents).map_err(corrupt)?;
+        files.insert(path, contents);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestBudgey;
+
+    #[test]
+    fn test_hooks_arent_bundled() {
+        let budgey = TestBudgey::new("home");
+        let hooks_path =
+            Path::new(&budgey.context.get_current_budget_path()).join(HOOKS_FOLDER_NAME);
+        fs::create_dir_all(&hooks_path).unwrap();
+        fs::write(hooks_path.join("pre-commit"), "#!/bin/sh\nexit 1\n").unwrap();
+        let bundle_path = budgey.beside("home.bundle").to_string_lossy().to_string();
+
+        let manifest = create_bundle(budgey.config(), "home", &bundle_path).unwrap();
+        assert!(manifest.files.iter().all(|file| !is_hook(&file.path)));
+        let other = BudgeyConfig::new(
+            &budgey.beside("other").to_string_lossy(),
+            &budgey.config().state_json_name,
+        );
+        import_bundle(&other, None, &bundle_path, None).unwrap();
+        assert!(Path::new(&other.get_budget_path("home"))
+            .join("main")
+            .exists());
+        assert!(!Path::new(&other.get_budget_path("home"))
+            .join(HOOKS_FOLDER_NAME)
+            .exists());
+    }
+
+    #[test]
+    fn test_verify_rejects_files_that_dont_match_the_manifest() {
+        let files = BTreeMap::from([
+            ("home.json".to_string(), b"{}".to_vec()),
+            ("main/main.json".to_string(), b"[]".to_vec()),
+        ]);
+        let manifest = BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            budget_name: "home".to_string(),
+            time_stamp: DateTime::UNIX_EPOCH,
+            files: files
+                .iter()
+                .map(|(path, contents)| bundled_file(path, contents))
+                .collect(),
+        };
+        assert_eq!(verify(&manifest, &files), Ok(()));
+
+        let mut changed = files.clone();
+        changed.insert("main/main.json".to_string(), b"[1]".to_vec());
+        assert_eq!(
+            verify(&manifest, &changed),
+            Err("main/main.json doesn't match its checksum".to_string())
+        );
+
+        let mut extra = files.clone();
+        extra.insert("side/side.json".to_string(), b"[]".to_vec());
+        assert_eq!(
+            verify(&manifest, &extra),
+            Err("side/side.json isn't listed in the manifest".to_string())
+        );
+
+        let mut missing = files.clone();
+        missing.remove("home.json");
+        assert_eq!(
+            verify(&manifest, &missing),
+            Err("home.json is missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_import_rejects_budget_names_outside_the_budgey_folder() {
+        let budgey = TestBudgey::new("home");
+        let config = budgey.config();
+        let files = BTreeMap::from([("home.json".to_string(), b"{}".to_vec())]);
+        let bundle_path = budgey.beside("hostile.bundle");
+        let write_bundle = |budget_name: &str| {
+            let manifest = BundleManifest {
+                format_version: BUNDLE_FORMAT_VERSION,
+                budget_name: budget_name.to_string(),
+                time_stamp: DateTime::UNIX_EPOCH,
+                files: files
+                    .iter()
+                    .map(|(path, contents)| bundled_file(path, contents))
+                    .collect(),
+            };
+            let encoder = GzEncoder::new(
+                fs::File::create(&bundle_path).unwrap(),
+                Compression::default(),
+            );
+            let mut archive = tar::Builder::new(encoder);
+            let manifest = serde_json::to_vec(&manifest).unwrap();
+            append_file(&mut archive, MANIFEST_PATH, &manifest).unwrap();
+            for (path, contents) in &files {
+                let path = concat_paths(BUDGET_FOLDER, path);
+                append_file(&mut archive, &path, contents).unwrap();
+            }
+            archive.into_inner().unwrap().finish().unwrap();
+            bundle_path.to_string_lossy().to_string()
+        };
+
+        for hostile in ["../escape", "nested/home", "/tmp/escape", ".."] {
+            assert!(matches!(
+                import_bundle(config, None, &write_bundle(hostile), None),
+                Err(BudgeyError::CorruptBundle(_))
+            ));
+        }
+        assert!(matches!(
+            import_bundle(config, None, &write_bundle("home"), Some("../escape")),
+            Err(BudgeyError::InvalidBudgetName(_))
+        ));
+        assert!(!budgey.beside("escape").exists());
+
+        // The budget file isn't valid, so the budget fails partway and is removed.
+        let bundle_path = write_bundle("home");
+        assert!(import_bundle(config, None, &bundle_path, Some("copy")).is_err());
+        assert!(!Path::new(&config.get_budget_path("copy")).exists());
+    }
+}
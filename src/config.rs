@@ -0,0 +1,384 @@
+//! Configuration read from TOML files, in the style of `git config`.
+//!
+//! Global settings live in `config.toml` in the budgey root folder. A `config.toml` in a budget's
+//! folder overrides them for that budget. Keys are written as `<section>.<name>`, such as
+//! `user.name` or `alias.ol`.
+//!
+//! Keys that aren't settings, such as misspelled ones, are ignored when settings are loaded, but
+//! can't be set.
+
+use std::{collections::BTreeMap, fs, io::ErrorKind};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::{
+    encryption,
+    error::{BudgeyError, BudgeyResult},
+    models::record_transaction::Author,
+    utils::{self, concat_paths},
+    BudgeyConfig, BudgeyContext,
+};
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// The environment variable that overrides `user.name`, for example when several people share
+/// one computer.
+pub const AUTHOR_NAME_VARIABLE: &str = "BUDGEY_AUTHOR_NAME";
+
+/// The environment variable that overrides `user.email`.
+pub const AUTHOR_EMAIL_VARIABLE: &str = "BUDGEY_AUTHOR_EMAIL";
+
+/// The settings that apply to a budget, after merging its config file over the global one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub core: CoreSettings,
+    pub user: UserSettings,
+    pub commit: CommitSettings,
+    /// Command aliases, such as `ol = "log --oneline"`.
+    pub alias: BTreeMap<String, String>,
+    /// Named numbers that can be used in amounts, such as `rent = 1200`.
+    pub constants: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoreSettings {
+    /// The currency shown after amounts, such as "EUR".
+    pub currency: Option<String>,
+    /// The chrono format string dates are shown with.
+    pub date_format: String,
+    /// Whether text output is coloured.
+    pub color: bool,
+}
+
+impl Default for CoreSettings {
+    fn default() -> Self {
+        Self {
+            currency: None,
+            date_format: "%Y-%m-%d %H:%M".to_string(),
+            color: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    /// The name of the person using budgey, recorded as the author of their records.
+    pub name: Option<String>,
+    /// The email of the person using budgey, recorded along with their name.
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitSettings {
+    /// The commit message used when none is given. `{pile}` and `{date}` are replaced with the
+    /// pile's name and the current date.
+    pub template: Option<String>,
+}
+
+impl Settings {
+    /// Shows an amount followed by the currency, if one is set.
+    pub fn format_amount(&self, amount: f32) -> String {
+        match &self.core.currency {
+            Some(currency) => format!("{} {}", amount, currency),
+            None => amount.to_string(),
+        }
+    }
+
+    /// Shows a time stamp in local time in the configured date format.
+    pub fn format_timestamp(&self, time_stamp: &DateTime<Utc>) -> String {
+        utils::format_timestamp(time_stamp, &self.core.date_format)
+    }
+
+    /// The author of new records, from the environment or `user.name` and `user.email`.
+    /// A name from the environment isn't paired with the configured email. None if no name is set.
+    pub fn author(&self) -> Option<Author> {
+        let from_environment = |variable| std::env::var(variable).ok().filter(|v| !v.is_empty());
+        let email = from_environment(AUTHOR_EMAIL_VARIABLE);
+        match from_environment(AUTHOR_NAME_VARIABLE) {
+            Some(name) => Some(Author::new(&name, email.as_deref())),
+            None => Some(Author::new(
+                self.user.name.as_ref()?,
+                email
+                    .as_ref()
+                    .or(self.user.email.as_ref())
+                    .map(String::as_str),
+            )),
+        }
+    }
+
+    /// The default commit message for the pile, if a template is set.
+    pub fn commit_message(&self, pile_name: &str) -> Option<String> {
+        let date = utils::format_timestamp(&Utc::now(), &self.core.date_format);
+        self.commit.template.as_ref().map(|template| {
+            template
+                .replace("{pile}", pile_name)
+                .replace("{date}", &date)
+        })
+    }
+}
+
+/// A single config file and the keys set in it.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    path: String,
+    table: Table,
+}
+
+impl ConfigFile {
+    /// Reads a config file. A file that doesn't exist has no keys set.
+    pub fn read(path: &str) -> anyhow::Result<Self> {
+        let table = match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str::<Table>(&contents).map_err(|e| {
+                BudgeyError::InvalidConfig(format!("couldn't parse {}: {}", path, e))
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Table::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: path.to_string(),
+            table,
+        })
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        fs::write(&self.path, toml::to_string(&self.table)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let (section, name) = split_key(key).ok()?;
+        self.table.get(section)?.get(name)
+    }
+
+    /// Sets a key, checking it is a setting and the value is valid for it.
+    pub fn set(&mut self, key: &str, value: Value) -> BudgeyResult<()> {
+        let (section, name) = split_key(key)?;
+        if self
+            .table
+            .get(section)
+            .is_some_and(|section_table| !section_table.is_table())
+        {
+            return Err(BudgeyError::InvalidConfig(format!(
+                "{} is not a section",
+                section
+            )));
+        }
+        let check = |value: &Value| {
+            let table = Table::from_iter([(
+                section.to_string(),
+                Value::Table(Table::from_iter([(name.to_string(), value.clone())])),
+            )]);
+            match settings_from_table(table)?.1.is_empty() {
+                true => Ok(()),
+                false => Err(BudgeyError::InvalidConfig(format!(
+                    "{} is not a setting",
+                    key
+                ))),
+            }
+        };
+        let value = match (check(&value), &value) {
+            (Ok(()), _) => value,
+            // A number given for a text setting, such as a numeric template, is kept as text.
+            (Err(BudgeyError::InvalidConfig(_)), Value::Integer(_) | Value::Float(_))
+                if check(&Value::String(display_value(&value))).is_ok() =>
+            {
+                Value::String(display_value(&value))
+            }
+            (Err(e), _) => return Err(e),
+        };
+        if let Value::Table(section_table) = self
+            .table
+            .entry(section)
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            section_table.insert(name.to_string(), value);
+        }
+        Ok(())
+    }
+
+    /// Removes a key, returning whether it was set.
+    pub fn unset(&mut self, key: &str) -> bool {
+        let Ok((section, name)) = split_key(key) else {
+            return false;
+        };
+        let Some(Value::Table(section_table)) = self.table.get_mut(section) else {
+            return false;
+        };
+        let removed = section_table.remove(name).is_some();
+        if section_table.is_empty() {
+            self.table.remove(section);
+        }
+        removed
+    }
+
+    /// Every key set in the file with its value, in key order.
+    pub fn entries(&self) -> BTreeMap<String, Value> {
+        flatten(&self.table)
+    }
+}
+
+/// The path of the global config file.
+pub fn global_config_path(config: &BudgeyConfig) -> String {
+    concat_paths(&config.root_path, CONFIG_FILE_NAME)
+}
+
+/// The path of a budget's config file.
+pub fn budget_config_path(config: &BudgeyConfig, budget_name: &str) -> String {
+    concat_paths(&config.get_budget_path(budget_name), CONFIG_FILE_NAME)
+}
+
+/// Reads the config file a key is set in: the global one, or the focused budget's. An encrypted
+/// budget can't be given a config file of its own, since it would be kept in plain text.
+pub fn file_to_set(context: &BudgeyContext, global: bool) -> BudgeyResult<ConfigFile> {
+    if global {
+        return Ok(ConfigFile::read(&global_config_path(context.config()))?);
+    }
+    let budget_name = context.get_current_budget_name();
+    if encryption::is_encrypted(&context.get_current_budget_path()) {
+        return Err(BudgeyError::EncryptedBudgetConfig(budget_name));
+    }
+    Ok(ConfigFile::read(&budget_config_path(
+        context.config(),
+        &budget_name,
+    ))?)
+}
+
+/// Loads the settings for a budget, or just the global settings if no budget is given, along with
+/// the keys that were ignored because they aren't settings.
+pub fn load_settings(
+    config: &BudgeyConfig,
+    budget_name: Option<&str>,
+) -> anyhow::Result<(Settings, Vec<String>)> {
+    let mut table = ConfigFile::read(&global_config_path(config))?.table;
+    if let Some(budget_name) = budget_name {
+        let budget_table = ConfigFile::read(&budget_config_path(config, budget_name))?.table;
+        for (section, values) in budget_table {
+            match (table.get_mut(&section), values) {
+                (Some(Value::Table(existing)), Value::Table(values)) => existing.extend(values),
+                (_, values) => {
+                    table.insert(section, values);
+                }
+            }
+        }
+    }
+    Ok(settings_from_table(table)?)
+}
+
+/// Every setting with its value, including defaults, in key order.
+pub fn settings_entries(settings: &Settings) -> anyhow::Result<BTreeMap<String, Value>> {
+    match Value::try_from(settings)? {
+        Value::Table(table) => Ok(flatten(&table)),
+        _ => Ok(BTreeMap::new()),
+    }
+}
+
+/// Parses a value given on the command line. "true" and "false" are booleans, numbers are
+/// integers or floats, anything else is a string.
+pub fn parse_value(text: &str) -> Value {
+    match text {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => {
+            if let Ok(number) = text.parse::<i64>() {
+                Value::Integer(number)
+            } else if let Some(number) = text.parse::<f64>().ok().filter(|n| n.is_finite()) {
+                Value::Float(number)
+            } else {
+                Value::String(text.to_string())
+            }
+        }
+    }
+}
+
+/// Shows a value the way it is given on the command line, without quotes around strings.
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Reads settings from a table, along with the keys in it that aren't settings.
+fn settings_from_table(table: Table) -> BudgeyResult<(Settings, Vec<String>)> {
+    let invalid = |message: String| BudgeyError::InvalidConfig(message);
+    let settings = Value::Table(table.clone())
+        .try_into::<Settings>()
+        .map_err(|e| invalid(e.message().to_string()))?;
+    // Every setting given a value is written back, so any other key was ignored.
+    let known = settings_entries(&settings).map_err(|e| invalid(e.to_string()))?;
+    let unknown = flatten(&table)
+        .into_keys()
+        .filter(|key| !known.contains_key(key))
+        .collect();
+    Ok((settings, unknown))
+}
+
+fn split_key(key: &str) -> BudgeyResult<(&str, &str)> {
+    key.split_once('.')
+        .filter(|(section, name)| !section.is_empty() && !name.is_empty())
+        .ok_or_else(|| {
+            BudgeyError::InvalidConfig(format!("{} is not a key. Use <section>.<name>.", key))
+        })
+}
+
+fn flatten(table: &Table) -> BTreeMap<String, Value> {
+    table
+        .iter()
+        .flat_map(|(section, values)| match values {
+            Value::Table(values) => values
+                .iter()
+                .map(|(name, value)| (format!("{}.{}", section, name), value.clone()))
+                .collect::<Vec<_>>(),
+            value => vec![(section.to_string(), value.clone())],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_rejects_unknown_and_mistyped_keys() {
+        let mut file = ConfigFile {
+            path: String::new(),
+            table: Table::new(),
+        };
+        assert!(file.set("user.name", parse_value("Sam")).is_ok());
+        assert!(file.set("core.colour", parse_value("false")).is_err());
+        assert!(file.set("core.color", parse_value("nope")).is_err());
+        assert!(file.set("core.storage", parse_value("json")).is_err());
+        assert!(file.set("alias.ol", parse_value("log --oneline")).is_ok());
+        assert!(file.set("commit.template", parse_value("2024")).is_ok());
+        assert!(file.set("constants.rent", parse_value("1200")).is_ok());
+        assert!(file.set("constants.fee", parse_value("fifty")).is_err());
+        assert_eq!(
+            vec!["alias.ol", "commit.template", "constants.rent", "user.name"],
+            file.entries().into_keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_are_ignored_when_loading() {
+        let table = toml::from_str::<Table>(
+            "colour = false\n[core]\ncolour = false\ncurrency = \"EUR\"\n[alias]\nol = \"log\"",
+        )
+        .unwrap();
+        let (settings, unknown) = settings_from_table(table).unwrap();
+        assert_eq!(Some("EUR"), settings.core.currency.as_deref());
+        assert_eq!(vec!["colour", "core.colour"], unknown);
+
+        let mut file = ConfigFile {
+            path: String::new(),
+            table: toml::from_str("[core]\ncolour = false").unwrap(),
+        };
+        assert!(file.set("user.name", parse_value("Sam")).is_ok());
+        assert!(file.unset("core.colour"));
+    }
+}
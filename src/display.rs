@@ -0,0 +1,108 @@
+use budgey::models::record_transaction::{Record, Transaction, TransactionType};
+use colored::{ColoredString, Colorize};
+
+use crate::output::Output;
+
+/// Prints a record in the format used by the record log.
+pub fn print_record(record: &Record, output: &Output) {
+    let record_indicator = "*".bold();
+    let separators = "|".bold();
+    let message = record.message.to_string().yellow();
+
+    output.println(format!(
+        "{} {} ",
+        record_indicator,
+        record.short_id().purple()
+    ));
+    output.println(&separators);
+    output.println(format!(
+        "{}     Amount after record: {}",
+        separators,
+        signed_amount(record.amount_after_record, output)
+    ));
+    output.println(format!("{}     Message: {}", separators, message));
+    if let Some(author) = &record.author {
+        output.println(format!("{}     Author: {}", separators, author));
+    }
+    output.println(format!(
+        "{}     Date: {}",
+        separators,
+        output.timestamp(&record.time_stamp)
+    ));
+
+    output.println(&separators);
+}
+
+/// Prints a record on a single line.
+pub fn print_record_oneline(record: &Record, output: &Output) {
+    output.println(format!(
+        "{} {} {}",
+        record.short_id().purple(),
+        signed_amount(record.amount_after_record, output),
+        record.message.yellow()
+    ));
+}
+
+/// Colours an amount green with a leading '+' if positive, red otherwise.
+pub fn signed_amount(amount: f32, output: &Output) -> ColoredString {
+    if amount > 0.0 {
+        format!("+{}", output.amount(amount)).green()
+    } else {
+        output.amount(amount).red()
+    }
+}
+
+/// The coloured sign shown in front of a transaction's amount.
+pub fn transaction_sign(transaction: &Transaction) -> ColoredString {
+    match transaction.transaction_type {
+        TransactionType::Add => "+".green(),
+        TransactionType::Withdraw => "-".red(),
+        TransactionType::Init => "~".white(),
+    }
+}
+
+/// Prints a record's details along with every transaction it contains.
+pub fn print_record_detail(record: &Record, output: &Output) {
+    output.println(format!("{} {}", "record".bold(), record.id.purple()));
+    if let Some(author) = &record.author {
+        output.println(format!("Author:  {}", author));
+    }
+    output.println(format!("Date:    {}", output.timestamp(&record.time_stamp)));
+    output.println(format!("Message: {}", record.message.yellow()));
+    if let Some(source) = &record.cherry_picked_from {
+        output.println(format!("Cherry-picked from: {}", source.purple()));
+    }
+    output.println(format!(
+        "Balance before: {}",
+        output.amount(record.amount_before_record())
+    ));
+    output.println(format!(
+        "Balance after:  {}",
+        output.amount(record.amount_after_record)
+    ));
+    output.println("Transactions:");
+    for transaction in &record.transactions {
+        print_transaction(transaction, output);
+    }
+}
+
+/// Prints a single transaction line with its sign, amount and note.
+pub fn print_transaction(transaction: &Transaction, output: &Output) {
+    let note = transaction.note.as_deref().unwrap_or_default();
+    output.println(format!(
+        "{}     {}{}    {}{}",
+        "|".bold(),
+        transaction_sign(transaction),
+        output.amount(transaction.amount),
+        note.yellow(),
+        transaction_date(transaction)
+    ));
+}
+
+/// The day a transaction was dated with `--date`, shown after its note.
+pub fn transaction_date(transaction: &Transaction) -> ColoredString {
+    match transaction.date {
+        Some(date) => format!(" (dated {})", date.format("%Y-%m-%d")).cyan(),
+        None => "".normal(),
+    }
+}
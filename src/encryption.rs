@@ -0,0 +1,428 @@
+//! Encryption at rest for budgets.
+//!
+//! An encrypted budget has a key file in its folder holding the salt used to derive its key from
+//! a passphrase with Argon2id. Its budget and pile files are then written as a single line of
+//! text: a prefix followed by the base64 of a random nonce and the XChaCha20-Poly1305 ciphertext.
+//! Files without the prefix are read as plain JSON, so budgets can be converted in place.
+//!
+//! Only the budget and pile files are encrypted. A budget with settings in its own `config.toml`
+//! can't be encrypted, and an encrypted budget can't be given one, so its settings are never kept in plain
+//! text next to it. Encrypted budgets are also kept out of the undo journal.
+//!
+//! Keys are cached for the rest of the process once derived. `unlock` also caches a key for the
+//! session, in a file only the user can read in the user's runtime folder, until it expires or
+//! `lock` is run. Without a runtime folder, keys are only cached for the session if a folder is
+//! chosen with `BUDGEY_SESSION_DIR`, since the shared temporary folder isn't private.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{ErrorKind, IsTerminal},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config,
+    error::{BudgeyError, BudgeyResult},
+    journal,
+    models::budget::Budget,
+    utils::{concat_paths, create_json_path},
+    BudgeyContext,
+};
+
+/// The name of the key file in an encrypted budget's folder.
+pub const KEY_FILE_NAME: &str = ".encryption";
+
+/// The environment variable a passphrase is read from before prompting for one.
+pub const PASSPHRASE_VARIABLE: &str = "BUDGEY_PASSPHRASE";
+
+/// The environment variable naming the folder session keys are kept in when there is no
+/// `XDG_RUNTIME_DIR`.
+pub const SESSION_FOLDER_VARIABLE: &str = "BUDGEY_SESSION_DIR";
+
+const ENCRYPTED_PREFIX: &str = "budgey-encrypted:v1:";
+
+/// Encrypted with the key and stored in the key file, to tell a wrong passphrase from a right one.
+const CHECK_TEXT: &str = "budgey";
+
+const SALT_LENGTH: usize = 16;
+
+type Key = [u8; 32];
+
+/// Keys derived or unlocked by this process, keyed by budget folder.
+static UNLOCKED: OnceLock<Mutex<BTreeMap<String, Key>>> = OnceLock::new();
+
+/// How a budget's key is derived from its passphrase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyFile {
+    kdf: String,
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    check: String,
+}
+
+/// A key cached for the session.
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionKey {
+    key: String,
+    expires: DateTime<Utc>,
+}
+
+/// Whether the budget in the given folder is encrypted.
+pub fn is_encrypted(budget_path: &str) -> bool {
+    Path::new(&key_file_path(budget_path)).exists()
+}
+
+/// Reads a file of the budget in the given folder, decrypting it if it is encrypted.
+pub fn read_file(budget_path: &str, path: &str) -> anyhow::Result<String> {
+    let contents = fs::read_to_string(path)?;
+    let Some(encoded) = contents.trim_end().strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(contents);
+    };
+    let key = key(budget_path)?;
+    Ok(decrypt(&key, encoded).ok_or_else(|| BudgeyError::Undecryptable(path.to_string()))?)
+}
+
+/// Writes a file of the budget in the given folder, encrypting it if the budget is encrypted.
+pub fn write_file(budget_path: &str, path: &str, contents: &str) -> anyhow::Result<()> {
+    if is_encrypted(budget_path) {
+        let key = key(budget_path)?;
+        fs::write(path, encrypt(&key, contents))?;
+    } else {
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+/// Encrypts the focused budget's files with a key derived from the passphrase, and drops the
+/// journal entries that hold plain copies of them.
+pub fn encrypt_budget(context: &BudgeyContext, passphrase: &str) -> BudgeyResult<()> {
+    check_can_encrypt(context)?;
+    let budget_path = context.get_current_budget_path();
+    let files = read_data_files(context)?;
+
+    let params = argon2::Params::default();
+    let mut salt = [0; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(
+        passphrase,
+        &salt,
+        params.m_cost(),
+        params.t_cost(),
+        params.p_cost(),
+    )?;
+    let key_file = KeyFile {
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(salt),
+        memory_kib: params.m_cost(),
+        iterations: params.t_cost(),
+        parallelism: params.p_cost(),
+        check: encrypt(&key, CHECK_TEXT),
+    };
+    fs::write(
+        key_file_path(&budget_path),
+        serde_json::to_string(&key_file).map_err(anyhow::Error::from)?,
+    )?;
+    cache_key(&budget_path, key);
+
+    for (path, contents) in &files {
+        write_file(&budget_path, path, contents)?;
+    }
+    journal::forget_budget(context.config(), &context.get_current_budget_name())?;
+    Ok(())
+}
+
+/// Checks that the focused budget can be encrypted: it isn't already, and has no config file of
+/// its own, which would stay in plain text.
+pub fn check_can_encrypt(context: &BudgeyContext) -> BudgeyResult<()> {
+    let budget_name = context.get_current_budget_name();
+    if is_encrypted(&context.get_current_budget_path()) {
+        return Err(BudgeyError::AlreadyEncrypted(budget_name));
+    }
+    let config_path = config::budget_config_path(context.config(), &budget_name);
+    if !config::ConfigFile::read(&config_path)?.entries().is_empty() {
+        return Err(BudgeyError::BudgetHasConfig(budget_name));
+    }
+    Ok(())
+}
+
+/// Writes the focused budget's files back as plain JSON and removes its key file.
+pub fn decrypt_budget(context: &BudgeyContext) -> BudgeyResult<()> {
+    let budget_path = context.get_current_budget_path();
+    if !is_encrypted(&budget_path) {
+        return Err(BudgeyError::NotEncrypted(context.get_current_budget_name()));
+    }
+    let files = read_data_files(context)?;
+    // Plain files are read as they are, so the budget stays readable while it is converted, and
+    // the key file is only removed once every file has been written back.
+    for (path, contents) in &files {
+        fs::write(path, contents)?;
+        if fs::read_to_string(path)? != *contents {
+            return Err(anyhow::anyhow!("Couldn't write {} back as plain JSON", path).into());
+        }
+    }
+    fs::remove_file(key_file_path(&budget_path))?;
+    lock(&budget_path)?;
+    Ok(())
+}
+
+/// Checks the passphrase of the budget in the given folder and caches its key for the session,
+/// for the given number of minutes.
+pub fn unlock(budget_path: &str, passphrase: &str, minutes: u64) -> BudgeyResult<()> {
+    let key_file = read_key_file(budget_path)?;
+    let key = key_from_passphrase(&key_file, passphrase)?;
+    let expires = Utc::now() + chrono::TimeDelta::minutes(minutes as i64);
+    let session_key = SessionKey {
+        key: BASE64.encode(key),
+        expires,
+    };
+    let path = session_key_path(budget_path).ok_or(BudgeyError::NoSessionFolder)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_private(
+        &path,
+        &serde_json::to_string(&session_key).map_err(anyhow::Error::from)?,
+    )?;
+    cache_key(budget_path, key);
+    Ok(())
+}
+
+/// Forgets the key of the budget in the given folder, returning whether it was cached for the
+/// session.
+pub fn lock(budget_path: &str) -> std::io::Result<bool> {
+    unlocked().remove(budget_path);
+    let Some(path) = session_key_path(budget_path) else {
+        return Ok(false);
+    };
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads a passphrase from the environment, or prompts for one if budgey is run from a terminal.
+pub fn read_passphrase(prompt: &str) -> BudgeyResult<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VARIABLE) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(BudgeyError::NoPassphrase);
+    }
+    Ok(rpassword::prompt_password(prompt)?)
+}
+
+/// The plain contents of the focused budget's budget and pile files, keyed by path.
+fn read_data_files(context: &BudgeyContext) -> anyhow::Result<Vec<(String, String)>> {
+    let budget_path = context.get_current_budget_path();
+    let budget_json_path = context.get_current_budget_json_path();
+    let budget_json = read_file(&budget_path, &budget_json_path)?;
+    let budget = serde_json::from_str::<Budget>(&budget_json)?;
+    let mut files = vec![(budget_json_path, budget_json)];
+    for pile_name in &budget.pile_names {
+        let pile_json_path = create_json_path(&concat_paths(&budget_path, pile_name), pile_name);
+        let pile_json = read_file(&budget_path, &pile_json_path)?;
+        files.push((pile_json_path, pile_json));
+    }
+    Ok(files)
+}
+
+/// The key of the budget in the given folder, from this process, the session or a passphrase.
+fn key(budget_path: &str) -> BudgeyResult<Key> {
+    if let Some(key) = unlocked().get(budget_path) {
+        return Ok(*key);
+    }
+    if let Some(key) = read_session_key(budget_path) {
+        cache_key(budget_path, key);
+        return Ok(key);
+    }
+    let key_file = read_key_file(budget_path)?;
+    let budget_name = Path::new(budget_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let passphrase = read_passphrase(&format!("Passphrase for budget {}: ", budget_name))?;
+    let key = key_from_passphrase(&key_file, &passphrase)?;
+    cache_key(budget_path, key);
+    Ok(key)
+}
+
+fn key_from_passphrase(key_file: &KeyFile, passphrase: &str) -> BudgeyResult<Key> {
+    let salt = BASE64
+        .decode(&key_file.salt)
+        .map_err(|e| anyhow::anyhow!("Invalid key file salt: {}", e))?;
+    let key = derive_key(
+        passphrase,
+        &salt,
+        key_file.memory_kib,
+        key_file.iterations,
+        key_file.parallelism,
+    )?;
+    match key_file
+        .check
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|check| decrypt(&key, check))
+    {
+        Some(check) if check == CHECK_TEXT => Ok(key),
+        _ => Err(BudgeyError::WrongPassphrase),
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> anyhow::Result<Key> {
+    let params = argon2::Params::new(memory_kib, iterations, parallelism, None)
+        .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = Key::default();
+    argon2
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Couldn't derive the key: {}", e))?;
+    Ok(key)
+}
+
+fn encrypt(key: &Key, contents: &str) -> String {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, contents.as_bytes())
+        .expect("encrypting into a vector can't fail");
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(data))
+}
+
+/// Decrypts what follows the prefix, or None if the key is wrong or the data was changed.
+fn decrypt(key: &Key, encoded: &str) -> Option<String> {
+    let data = BASE64.decode(encoded).ok()?;
+    let nonce_length = XNonce::default().len();
+    if data.len() < nonce_length {
+        return None;
+    }
+    let (nonce, ciphertext) = data.split_at(nonce_length);
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let contents = cipher.decrypt(XNonce::from_slice(nonce), ciphertext).ok()?;
+    String::from_utf8(contents).ok()
+}
+
+fn key_file_path(budget_path: &str) -> String {
+    concat_paths(budget_path, KEY_FILE_NAME)
+}
+
+fn read_key_file(budget_path: &str) -> BudgeyResult<KeyFile> {
+    let contents = match fs::read_to_string(key_file_path(budget_path)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(BudgeyError::NotEncrypted(budget_path.to_string()));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(serde_json::from_str(&contents).map_err(anyhow::Error::from)?)
+}
+
+fn unlocked() -> std::sync::MutexGuard<'static, BTreeMap<String, Key>> {
+    UNLOCKED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn cache_key(budget_path: &str, key: Key) {
+    unlocked().insert(budget_path.to_string(), key);
+}
+
+/// Where the session key of the budget in the given folder is kept: the user's runtime folder, or
+/// the folder chosen with `BUDGEY_SESSION_DIR`. None if there is neither.
+fn session_key_path(budget_path: &str) -> Option<PathBuf> {
+    let folder = [SESSION_FOLDER_VARIABLE, "XDG_RUNTIME_DIR"]
+        .into_iter()
+        .find_map(|variable| std::env::var_os(variable).filter(|folder| !folder.is_empty()))?;
+    let budget_hash = hex::encode(Sha256::digest(budget_path.as_bytes()));
+    Some(PathBuf::from(folder).join(format!("budgey-{}.key", &budget_hash[..16])))
+}
+
+fn read_session_key(budget_path: &str) -> Option<Key> {
+    let path = session_key_path(budget_path)?;
+    let session_key = serde_json::from_str::<SessionKey>(&fs::read_to_string(&path).ok()?).ok()?;
+    if session_key.expires <= Utc::now() {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    BASE64.decode(session_key.key).ok()?.try_into().ok()
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+    let _ = fs::remove_file(path);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestBudgey;
+
+    #[test]
+    fn test_encrypt_round_trip_and_wrong_key() {
+        let key = [7; 32];
+        let encrypted = encrypt(&key, "{\"name\":\"main\"}");
+        let encoded = encrypted.strip_prefix(ENCRYPTED_PREFIX).unwrap();
+        assert_eq!(
+            decrypt(&key, encoded).as_deref(),
+            Some("{\"name\":\"main\"}")
+        );
+        assert_eq!(decrypt(&[8; 32], encoded), None);
+        assert_ne!(encrypt(&key, "{}"), encrypt(&key, "{}"));
+    }
+
+    #[test]
+    fn test_budget_config_files_and_encryption_are_kept_apart() {
+        let budgey = TestBudgey::new("home");
+        let context = &budgey.context;
+        let config_path = config::budget_config_path(context.config(), "home");
+        fs::write(&config_path, "[user]\nname = \"Sam\"\n").unwrap();
+        assert!(matches!(
+            check_can_encrypt(context),
+            Err(BudgeyError::BudgetHasConfig(_))
+        ));
+
+        // A file left empty by `config unset` holds nothing.
+        fs::write(&config_path, "").unwrap();
+        check_can_encrypt(context).unwrap();
+        fs::write(key_file_path(&context.get_current_budget_path()), "{}").unwrap();
+        assert!(matches!(
+            config::file_to_set(context, false),
+            Err(BudgeyError::EncryptedBudgetConfig(_))
+        ));
+        assert!(config::file_to_set(context, true).is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::models::pile::RecordLookupError;
+
+/// Errors returned by budgey operations.
+#[derive(thiserror::Error, Debug)]
+pub enum BudgeyError {
+    #[error("Pile doesn't exist in the current budget. Specify another name.")]
+    PileNotFound(String),
+    #[error("Pile \"{0}\" already exists, try selecting a different name or deleting the pile")]
+    PileAlreadyExists(String),
+    #[error("Cannot delete the main pile of a budget.\n\nIf you want to delete the budget, type `budget delete <name>`")]
+    CannotDeleteMainPile,
+    #[error("Cannot rebase a pile onto itself.")]
+    RebaseOntoSelf,
+    #[error("No staged transactions to commit. Add some transactions first.")]
+    NoStagedTransactions,
+    #[error("The focused pile has staged transactions. Commit or restore them first.")]
+    UncommittedTransactions,
+    #[error("Invalid amount or expression {0:?}: {1}")]
+    InvalidAmount(String, String),
+    #[error("Invalid split: {0}")]
+    InvalidSplit(String),
+    #[error("Invalid allocation rule: {0}")]
+    InvalidAllocationRule(String),
+    #[error("Allocation rule {0} doesn't exist. Add it with `budgey allocation set <name> <pile>=<share>...`")]
+    AllocationRuleNotFound(String),
+    #[error("Couldn't choose an allocation rule. Name one with --rule, or add one with `budgey allocation set <name> <pile>=<share>...`")]
+    NoAllocationRule,
+    #[error("Can't allocate the amount: {0}")]
+    CannotAllocate(String),
+    #[error("Invalid goal: {0}")]
+    InvalidGoal(String),
+    #[error("Pile {0} has no goal")]
+    NoGoal(String),
+    #[error("Invalid policy: {0}")]
+    InvalidPolicy(String),
+    #[error("{0}. Pass --force to go ahead anyway.")]
+    PolicyViolation(String),
+    #[error(transparent)]
+    RecordLookup(#[from] RecordLookupError),
+    #[error("Tag \"{0}\" already exists, try selecting a different name or deleting the tag")]
+    TagAlreadyExists(String),
+    #[error("Tag {0} doesn't exist, specify another name")]
+    TagNotFound(String),
+    #[error("No records to tag in the focused pile")]
+    NoRecordsToTag,
+    #[error("Record {0} is already in the focused pile's history")]
+    AlreadyInHistory(String),
+    #[error("The {0} hook rejected the operation ({1})")]
+    HookRejected(String, String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Can't undo {0} command(s), the journal has {1}")]
+    NotEnoughJournalEntries(usize, usize),
+    #[error("Can't undo `{0}`, it changed budget {1}, which is now encrypted")]
+    EncryptedJournalEntry(String, String),
+    #[error("Remote {0} doesn't exist. Add it with `budgey remote add <name> <path>`")]
+    RemoteNotFound(String),
+    #[error("Remote {0} already exists")]
+    RemoteAlreadyExists(String),
+    #[error(
+        "Couldn't choose a remote. Name one, or add one with `budgey remote add <name> <path>`"
+    )]
+    NoRemote,
+    #[error("Couldn't find a budget at {0}")]
+    RemoteBudgetNotFound(String),
+    #[error("The remote has several budgets: {0}. Choose one with --budget.")]
+    AmbiguousRemoteBudget(String),
+    #[error("{0} is encrypted, so it can't be pushed, pulled or cloned. Copy it with `budgey bundle` instead")]
+    EncryptedRemoteBudget(String),
+    #[error("A budget named {0} already exists")]
+    BudgetAlreadyExists(String),
+    #[error("{0:?} can't be a budget name. Use a name without path separators")]
+    InvalidBudgetName(String),
+    #[error("Budget {0} doesn't exist")]
+    BudgetNotFound(String),
+    #[error("The bundle is corrupt: {0}")]
+    CorruptBundle(String),
+    #[error("The bundle uses format version {0}, which this version of budgey can't read")]
+    UnsupportedBundleVersion(u32),
+    #[error("Budget {0} is already encrypted")]
+    AlreadyEncrypted(String),
+    #[error("Budget {0} has settings in its config.toml, which can't be encrypted. Move its settings to the global config with `budgey config set --global` and remove them first")]
+    BudgetHasConfig(String),
+    #[error("Budget {0} is encrypted, but its config.toml wouldn't be. Set the key with --global instead")]
+    EncryptedBudgetConfig(String),
+    #[error("There is no private folder to keep the key in for the session. Set XDG_RUNTIME_DIR, or BUDGEY_SESSION_DIR to a folder only you can read")]
+    NoSessionFolder,
+    #[error("Budget {0} isn't encrypted. Encrypt it with `budgey encrypt`")]
+    NotEncrypted(String),
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error(
+        "The budget is encrypted. Set BUDGEY_PASSPHRASE or run `budgey unlock` from a terminal"
+    )]
+    NoPassphrase,
+    #[error("{0} couldn't be decrypted. It may have been changed or encrypted with another key")]
+    Undecryptable(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl BudgeyError {
+    /// Whether the error was caused by the request made rather than a failure while carrying it
+    /// out, for example naming a pile that doesn't exist.
+    pub fn is_usage(&self) -> bool {
+        match self {
+            BudgeyError::Io(_) => false,
+            // Operations wrap errors from reading files, such as a wrong passphrase.
+            BudgeyError::Other(e) => e
+                .downcast_ref::<BudgeyError>()
+                .is_some_and(BudgeyError::is_usage),
+            _ => true,
+        }
+    }
+}
+
+pub type BudgeyResult<T> = Result<T, BudgeyError>;
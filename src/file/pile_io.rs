@@ -2,6 +2,7 @@ use std::fs;
 
 use crate::{
     budget_management::update_budget,
+    encryption,
     models::{budget::Budget, pile::Pile},
     utils::{concat_paths, create_json_path},
     BudgeyContext,
@@ -42,14 +43,19 @@ impl PileIO for PileIOImpl {
             &new_pile.get_name(),
         );
         let pile_json_path = create_json_path(&pile_path, &new_pile.get_name());
-        fs::write(pile_json_path, serde_json::to_string(&new_pile)?)?;
+        encryption::write_file(
+            &self.context.get_current_budget_path(),
+            &pile_json_path,
+            &serde_json::to_string(&new_pile)?,
+        )?;
         Ok(())
     }
 
     fn get_pile(&self, pile_name: &str) -> anyhow::Result<Pile> {
         let pile_path = concat_paths(&self.context.get_current_budget_path(), pile_name);
         let pile_json_path = create_json_path(&pile_path, pile_name);
-        let pile_json = fs::read_to_string(pile_json_path)?;
+        let pile_json =
+            encryption::read_file(&self.context.get_current_budget_path(), &pile_json_path)?;
         Ok(serde_json::from_str::<Pile>(&pile_json)?)
     }
 
@@ -75,7 +81,12 @@ impl PileIO for PileIOImpl {
         fs::create_dir(pile_directory_path)?;
         let pile_file_path =
             create_json_path(&concat_paths(&current_budget_path, &pile_name), &pile_name);
-        fs::write(pile_file_path, serde_json::to_string(&pile)?)?;
+        encryption::write_file(
+            &current_budget_path,
+            &pile_file_path,
+            &serde_json::to_string(&pile)?,
+        )
+        .map_err(std::io::Error::other)?;
 
         Ok(())
     }
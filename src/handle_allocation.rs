@@ -0,0 +1,167 @@
+use std::io::{IsTerminal, Write};
+
+use budgey::{
+    allocation::{self, AllocationPlan},
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    models::allocation::{AllocationShare, AllocationTarget},
+    operations, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{
+    budgey_cli::{AllocateArgs, AllocationSubcommand},
+    output::Output,
+};
+
+pub fn handle_allocation(
+    context: &BudgeyContext,
+    subcommand: Option<AllocationSubcommand>,
+    budget_io: &impl BudgetIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    match subcommand {
+        Some(AllocationSubcommand::Set {
+            name,
+            targets,
+            minimums,
+        }) => {
+            let Some(rule) = output.check(allocation::set_rule(
+                context, &name, &targets, &minimums, budget_io,
+            ))?
+            else {
+                return Ok(());
+            };
+            output.println(format!(
+                "Set allocation rule {}: {}",
+                name.green(),
+                describe_targets(&rule.targets)
+            ));
+            output.data(json!({ "rule": name, "targets": rule.targets }))
+        }
+        Some(AllocationSubcommand::Remove { name }) => {
+            if output
+                .check(allocation::remove_rule(context, &name, budget_io))?
+                .is_none()
+            {
+                return Ok(());
+            }
+            output.println(format!("Removed allocation rule {}", name));
+            output.data(json!({ "rule": name }))
+        }
+        None => {
+            let budget = budget_io.get_current_budget(context)?;
+            if budget.allocation_rules.is_empty() {
+                output.println(
+                    "No allocation rules. Add one with `budgey allocation set <name> <pile>=<share>...`",
+                );
+            }
+            for (name, rule) in &budget.allocation_rules {
+                output.println(format!(
+                    "{} {}",
+                    name.green(),
+                    describe_targets(&rule.targets)
+                ));
+            }
+            output.data(json!({ "rules": budget.allocation_rules }))
+        }
+    }
+}
+
+pub fn handle_allocate(
+    context: &BudgeyContext,
+    args: &AllocateArgs,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let variables = operations::amount_variables(context, budget_io, pile_io)?;
+    let Some(amount) = output.check(operations::evaluate_amount(&args.amount, &variables))? else {
+        return Ok(());
+    };
+    let Some(plan) = output.check(allocation::plan_allocation(
+        context,
+        amount,
+        args.rule.as_deref(),
+        budget_io,
+    ))?
+    else {
+        return Ok(());
+    };
+    print_plan(&plan, output);
+
+    if args.dry_run {
+        return output.data(json!({ "plan": plan, "staged": false }));
+    }
+    if !args.yes {
+        if !std::io::stdin().is_terminal() {
+            output.fail("Pass --yes to stage the allocation without being asked");
+            return Ok(());
+        }
+        if !confirm("Stage these transactions? [y/N] ")? {
+            output.println("Nothing was staged.");
+            return output.data(json!({ "plan": plan, "staged": false }));
+        }
+    }
+
+    let result =
+        allocation::stage_allocation(context, &plan, args.note.as_deref(), budget_io, pile_io);
+    let Some(staged) = output.check(result)? else {
+        return Ok(());
+    };
+    output.println("Focus each pile and commit to record its part.");
+    output.data(json!({
+        "plan": plan,
+        "staged": true,
+        "piles": staged
+            .iter()
+            .map(|(pile, _)| json!({ "pile": pile.get_name(), "balance": pile.current_balance }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn print_plan(plan: &AllocationPlan, output: &Output) {
+    output.println(format!(
+        "Allocating {} by rule {}:",
+        output.amount(plan.amount),
+        plan.rule_name.green()
+    ));
+    for part in &plan.allocation.parts {
+        output.println(format!(
+            " {} {}: {}",
+            "*".bold(),
+            part.pile_name,
+            output.amount(part.amount)
+        ));
+    }
+    if plan.allocation.unallocated > 0.0 {
+        output.println(format!(
+            "{} left unallocated",
+            output.amount(plan.allocation.unallocated).yellow()
+        ));
+    }
+}
+
+fn describe_targets(targets: &[AllocationTarget]) -> String {
+    targets
+        .iter()
+        .map(|target| match (target.share, target.minimum) {
+            (AllocationShare::Fixed(_), _) | (_, None) => {
+                format!("{}={}", target.pile_name, target.share)
+            }
+            (share, Some(minimum)) => {
+                format!("{}={} (at least {})", target.pile_name, share, minimum)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Asks a yes or no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    eprint!("{}", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,47 @@
+use budgey::{budgey_state::BudgeyState, bundle, BudgeyConfig};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{budgey_cli::BundleSubcommand, output::Output};
+
+pub fn handle_bundle(
+    config: &BudgeyConfig,
+    local_state: Option<&BudgeyState>,
+    subcommand: BundleSubcommand,
+    output: &Output,
+) -> anyhow::Result<()> {
+    match subcommand {
+        BundleSubcommand::Create { budget, file } => {
+            let Some(manifest) = output.check(bundle::create_bundle(config, &budget, &file))?
+            else {
+                return Ok(());
+            };
+            output.println(format!(
+                "Bundled budget {} into {} ({} file(s))",
+                budget.green(),
+                file,
+                manifest.files.len()
+            ));
+            output.data(json!({ "file": file, "manifest": manifest }))
+        }
+        BundleSubcommand::Import { file, name } => {
+            let Some(manifest) = output.check(bundle::import_bundle(
+                config,
+                local_state,
+                &file,
+                name.as_deref(),
+            ))?
+            else {
+                return Ok(());
+            };
+            let budget = name.unwrap_or_else(|| manifest.budget_name.clone());
+            output.println(format!(
+                "Imported budget {} from {} ({} file(s)) and focused it",
+                budget.green(),
+                file,
+                manifest.files.len()
+            ));
+            output.data(json!({ "budget": budget, "manifest": manifest }))
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    operations, BudgeyContext,
+};
+use serde_json::json;
+
+use crate::output::Output;
+
+pub fn handle_cherry_pick(
+    context: &BudgeyContext,
+    record_id: &str,
+    from: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let Some(cherry_pick) = output.check(operations::cherry_pick(
+        context, record_id, from, budget_io, pile_io,
+    ))?
+    else {
+        return Ok(());
+    };
+
+    output.println(format!(
+        "Cherry-picked record {} from pile {} as {}. Balance: {}",
+        cherry_pick.source_record.short_id(),
+        cherry_pick.source_pile_name,
+        cherry_pick.new_record.short_id(),
+        output.amount(cherry_pick.new_record.amount_after_record)
+    ));
+    output.data(json!({
+        "pile": cherry_pick.pile.get_name(),
+        "source_pile": cherry_pick.source_pile_name,
+        "record": cherry_pick.new_record,
+    }))
+}
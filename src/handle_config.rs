@@ -0,0 +1,77 @@
+use budgey::{
+    config::{self, ConfigFile},
+    BudgeyContext,
+};
+use serde_json::json;
+
+use crate::{
+    budgey_cli::{ConfigScope, ConfigSubcommand},
+    output::Output,
+};
+
+pub fn handle_config(
+    context: &BudgeyContext,
+    subcommand: ConfigSubcommand,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let global_path = config::global_config_path(context.config());
+    let budget_path =
+        config::budget_config_path(context.config(), &context.get_current_budget_name());
+    let scope_path = |scope: &ConfigScope| {
+        if scope.global {
+            Some(global_path.clone())
+        } else if scope.budget {
+            Some(budget_path.clone())
+        } else {
+            None
+        }
+    };
+
+    match subcommand {
+        ConfigSubcommand::Get { key, scope } => {
+            let value = match scope_path(&scope) {
+                Some(path) => ConfigFile::read(&path)?.get(&key).cloned(),
+                None => config::settings_entries(context.settings())?.remove(&key),
+            };
+            let Some(value) = value else {
+                output.fail(format!("{} is not set", key));
+                return Ok(());
+            };
+            output.println(config::display_value(&value));
+            output.data(json!({ "key": key, "value": value }))
+        }
+        ConfigSubcommand::Set { key, value, global } => {
+            let Some(mut file) = output.check(config::file_to_set(context, global))? else {
+                return Ok(());
+            };
+            let value = config::parse_value(&value);
+            if output.check(file.set(&key, value.clone()))?.is_none() {
+                return Ok(());
+            }
+            file.write()?;
+            output.println(format!("Set {} to {}", key, config::display_value(&value)));
+            output.data(json!({ "key": key, "value": value, "global": global }))
+        }
+        ConfigSubcommand::Unset { key, global } => {
+            let path = if global { global_path } else { budget_path };
+            let mut file = ConfigFile::read(&path)?;
+            if !file.unset(&key) {
+                output.fail(format!("{} is not set in {}", key, path));
+                return Ok(());
+            }
+            file.write()?;
+            output.println(format!("Unset {}", key));
+            output.data(json!({ "key": key, "global": global }))
+        }
+        ConfigSubcommand::List { scope } => {
+            let entries = match scope_path(&scope) {
+                Some(path) => ConfigFile::read(&path)?.entries(),
+                None => config::settings_entries(context.settings())?,
+            };
+            for (key, value) in &entries {
+                output.println(format!("{}={}", key, config::display_value(value)));
+            }
+            output.data(json!({ "entries": entries }))
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    models::pile::Pile,
+    operations, utils, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{display, output::Output};
+
+pub fn handle_diff(
+    context: &BudgeyContext,
+    first: &str,
+    second: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    match second {
+        Some(second) => {
+            let piles = (
+                output.check(operations::get_pile(
+                    context,
+                    Some(first),
+                    budget_io,
+                    pile_io,
+                ))?,
+                output.check(operations::get_pile(
+                    context,
+                    Some(second),
+                    budget_io,
+                    pile_io,
+                ))?,
+            );
+            let (Some(first_pile), Some(second_pile)) = piles else {
+                return Ok(());
+            };
+            diff_piles(&first_pile, &second_pile, output)
+        }
+        None => {
+            let Some((from, to)) = first.split_once("..") else {
+                output.fail("Specify two piles, or a record range in the form <record>..<record>");
+                return Ok(());
+            };
+            let current_pile = pile_io.get_current_pile(&current_budget)?;
+            diff_records(
+                &current_pile,
+                current_budget.resolve_reference(from),
+                current_budget.resolve_reference(to),
+                output,
+            )
+        }
+    }
+}
+
+fn diff_piles(first: &Pile, second: &Pile, output: &Output) -> anyhow::Result<()> {
+    let shared = first.shared_history_len(second);
+
+    match first.records[..shared].last() {
+        Some(record) => output.println(format!(
+            "Shared history: {} record(s), last shared record {}",
+            shared,
+            record.id.purple()
+        )),
+        None => output.println("No shared history"),
+    }
+
+    for pile in [first, second] {
+        let own_records = &pile.records[shared..];
+        output.println(format!(
+            " --- Records only in {} ({}) ---",
+            pile.get_name(),
+            own_records.len()
+        ));
+        for record in own_records.iter().rev() {
+            display::print_record(record, output);
+        }
+    }
+
+    let difference = utils::round_to_two_decimals(first.current_balance - second.current_balance);
+    output.println(format!(
+        "Balance of {}: {}\nBalance of {}: {}\nDifference: {}",
+        first.get_name(),
+        output.amount(first.current_balance),
+        second.get_name(),
+        output.amount(second.current_balance),
+        display::signed_amount(difference, output)
+    ));
+    let piles = [first, second];
+    output.data(json!({
+        "shared_records": &first.records[..shared],
+        "piles": piles
+            .iter()
+            .map(|pile| json!({
+                "name": pile.get_name(),
+                "balance": pile.current_balance,
+                "own_records": &pile.records[shared..],
+            }))
+            .collect::<Vec<_>>(),
+        "difference": difference,
+    }))
+}
+
+fn diff_records(pile: &Pile, from: &str, to: &str, output: &Output) -> anyhow::Result<()> {
+    let position = |id_or_prefix: &str| {
+        let record = pile.resolve_record(id_or_prefix)?;
+        anyhow::Ok(pile.records.iter().position(|r| r.id == record.id))
+    };
+    let (from_index, to_index) = match (position(from), position(to)) {
+        (Ok(Some(from_index)), Ok(Some(to_index))) => (from_index, to_index),
+        (Err(e), _) | (_, Err(e)) => {
+            output.fail(e);
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    if from_index > to_index {
+        output.fail(format!(
+            "Record {} comes after {}. Swap the range.",
+            from, to
+        ));
+        return Ok(());
+    }
+
+    let records = &pile.records[from_index + 1..=to_index];
+    output.println(format!(" --- Transactions between {} and {} ---", from, to));
+    for record in records {
+        output.println(format!("{} {}", "*".bold(), record.short_id().purple()));
+        for transaction in &record.transactions {
+            display::print_transaction(transaction, output);
+        }
+    }
+    let change: f32 = records.iter().map(|record| record.balance_change()).sum();
+    let change = utils::round_to_two_decimals(change);
+    output.println(format!(
+        "Net change: {}",
+        display::signed_amount(change, output)
+    ));
+    output.data(json!({
+        "pile": pile.get_name(),
+        "records": records,
+        "net_change": change,
+    }))
+}
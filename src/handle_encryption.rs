@@ -0,0 +1,88 @@
+use budgey::{encryption, BudgeyContext};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::output::Output;
+
+pub fn handle_encrypt(context: &BudgeyContext, output: &Output) -> anyhow::Result<()> {
+    let budget_name = context.get_current_budget_name();
+    if output
+        .check(encryption::check_can_encrypt(context))?
+        .is_none()
+    {
+        return Ok(());
+    }
+    let Some(passphrase) = output.check(encryption::read_passphrase("New passphrase: "))? else {
+        return Ok(());
+    };
+    if passphrase.is_empty() {
+        output.fail("The passphrase can't be empty");
+        return Ok(());
+    }
+    if std::env::var_os(encryption::PASSPHRASE_VARIABLE).is_none() {
+        let Some(repeated) = output.check(encryption::read_passphrase("Repeat passphrase: "))?
+        else {
+            return Ok(());
+        };
+        if repeated != passphrase {
+            output.fail("The passphrases don't match");
+            return Ok(());
+        }
+    }
+    if output
+        .check(encryption::encrypt_budget(context, &passphrase))?
+        .is_none()
+    {
+        return Ok(());
+    }
+    output.println(format!(
+        "Encrypted budget {}. Keep the passphrase safe, it can't be recovered.",
+        budget_name.green()
+    ));
+    output.data(json!({ "budget": budget_name, "encrypted": true }))
+}
+
+pub fn handle_decrypt(context: &BudgeyContext, output: &Output) -> anyhow::Result<()> {
+    let budget_name = context.get_current_budget_name();
+    if output.check(encryption::decrypt_budget(context))?.is_none() {
+        return Ok(());
+    }
+    output.println(format!("Decrypted budget {}", budget_name.green()));
+    output.data(json!({ "budget": budget_name, "encrypted": false }))
+}
+
+pub fn handle_unlock(context: &BudgeyContext, minutes: u64, output: &Output) -> anyhow::Result<()> {
+    let budget_name = context.get_current_budget_name();
+    let budget_path = context.get_current_budget_path();
+    if !encryption::is_encrypted(&budget_path) {
+        output.fail(format!("Budget {} isn't encrypted", budget_name));
+        return Ok(());
+    }
+    let prompt = format!("Passphrase for budget {}: ", budget_name);
+    let Some(passphrase) = output.check(encryption::read_passphrase(&prompt))? else {
+        return Ok(());
+    };
+    if output
+        .check(encryption::unlock(&budget_path, &passphrase, minutes))?
+        .is_none()
+    {
+        return Ok(());
+    }
+    output.println(format!(
+        "Unlocked budget {} for {} minute(s)",
+        budget_name.green(),
+        minutes
+    ));
+    output.data(json!({ "budget": budget_name, "minutes": minutes }))
+}
+
+pub fn handle_lock(context: &BudgeyContext, output: &Output) -> anyhow::Result<()> {
+    let budget_name = context.get_current_budget_name();
+    let was_unlocked = encryption::lock(&context.get_current_budget_path())?;
+    if was_unlocked {
+        output.println(format!("Locked budget {}", budget_name.green()));
+    } else {
+        output.println(format!("Budget {} wasn't unlocked", budget_name));
+    }
+    output.data(json!({ "budget": budget_name, "was_unlocked": was_unlocked }))
+}
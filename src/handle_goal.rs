@@ -0,0 +1,99 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    models::goal::{GoalProgress, GoalStatus, SavingsGoal, RECENT_INFLOW_DAYS},
+    operations, utils, BudgeyContext,
+};
+use colored::{ColoredString, Colorize};
+use serde_json::json;
+
+use crate::{budgey_cli::GoalSubcommand, output::Output};
+
+pub fn handle_goal(
+    context: &BudgeyContext,
+    subcommand: Option<GoalSubcommand>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    match subcommand {
+        Some(GoalSubcommand::Set { pile, amount, by }) => {
+            let variables = operations::amount_variables(context, budget_io, pile_io)?;
+            let Some(target) = output.check(operations::evaluate_amount(&amount, &variables))?
+            else {
+                return Ok(());
+            };
+            let by = match utils::parse_date(&by) {
+                Ok(by) => by,
+                Err(e) => {
+                    output.fail(e);
+                    return Ok(());
+                }
+            };
+            let goal = SavingsGoal::new(target, by);
+            let result = operations::set_goal(context, &pile, &goal, budget_io, pile_io);
+            if output.check(result)?.is_none() {
+                return Ok(());
+            }
+            output.println(format!(
+                "Set a goal of {} by {} for pile {}",
+                output.amount(target),
+                by,
+                pile.green()
+            ));
+            output.data(json!({ "pile": pile, "goal": goal }))
+        }
+        Some(GoalSubcommand::Remove { pile }) => {
+            let result = operations::remove_goal(context, &pile, budget_io, pile_io);
+            let Some(goal) = output.check(result)? else {
+                return Ok(());
+            };
+            output.println(format!("Removed the goal of pile {}", pile.green()));
+            output.data(json!({ "pile": pile, "goal": goal }))
+        }
+        Some(GoalSubcommand::List) | None => {
+            let goals = operations::goal_progress(context, budget_io, pile_io)?;
+            if goals.is_empty() {
+                output.println(
+                    "No goals. Set one with `budgey goal set <pile> <amount> --by <date>`",
+                );
+            }
+            for progress in &goals {
+                print_progress(progress, output);
+            }
+            output.data(json!({ "goals": goals }))
+        }
+    }
+}
+
+fn print_progress(progress: &GoalProgress, output: &Output) {
+    output.println(format!(
+        "{} {} of {} ({}%) by {}",
+        progress.pile_name.green(),
+        output.amount(progress.balance),
+        output.amount(progress.goal.target),
+        progress.percent,
+        progress.goal.by
+    ));
+    let detail = match progress.status {
+        GoalStatus::Reached => "Reached".to_string(),
+        GoalStatus::Overdue => format!("{} still to save", output.amount(progress.remaining)),
+        GoalStatus::OnPace | GoalStatus::Behind => format!(
+            "{} to save in {} day(s): {} a month needed, {} a month over the last {} days",
+            output.amount(progress.remaining),
+            progress.days_left,
+            output.amount(progress.monthly_needed),
+            output.amount(progress.monthly_inflow),
+            RECENT_INFLOW_DAYS
+        ),
+    };
+    output.println(format!("  {}. {}", detail, status(progress.status)));
+}
+
+fn status(status: GoalStatus) -> ColoredString {
+    match status {
+        GoalStatus::Reached => "Goal reached".green(),
+        GoalStatus::OnPace => "On pace".green(),
+        GoalStatus::Behind => "Behind pace".yellow(),
+        GoalStatus::Overdue => "Overdue".red(),
+    }
+}
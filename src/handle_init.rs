@@ -1,4 +1,4 @@
-use crate::{
+use budgey::{
     budgey_state::BudgeyState,
     file::{
         budget_io::BudgetIO,
@@ -8,22 +8,24 @@ use crate::{
     models::{budget::Budget, pile::Pile},
     BudgeyConfig, BudgeyContext,
 };
+use serde_json::json;
+
+use crate::output::Output;
 
 pub fn handle_init(
     starting_budget_name: &str,
     config: &BudgeyConfig,
     state_io: &impl StateIO,
     budget_io: &impl BudgetIO,
+    output: &Output,
 ) -> anyhow::Result<()> {
     let budget_already_initialised = state_io.check_state_initialised()?;
 
     if budget_already_initialised {
-        println!(
-            "Budgey already initialised. Run `budgey budget new <budget name>` to create new budgets. "
-        );
+        output.fail("Budgey already initialised. Run `budgey budget new <budget name>` to create new budgets. ");
         return Ok(());
     }
-    println!("Initialising Budgey...");
+    output.println("Initialising Budgey...");
 
     let new_budget = Budget::new_init(starting_budget_name);
 
@@ -37,6 +39,6 @@ pub fn handle_init(
     let pile_io = PileIOImpl::new(&new_context);
     pile_io.create_new_pile(&Pile::default_main_pile())?;
 
-    println!("Budgey init finished. Run `budgey` to see help.");
-    Ok(())
+    output.println("Budgey init finished. Run `budgey` to see help.");
+    output.data(json!({ "budget": starting_budget_name, "pile": "main" }))
 }
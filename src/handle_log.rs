@@ -0,0 +1,139 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    log_graph::{self, GraphLine},
+    models::pile::Pile,
+    operations::{self, RecordQuery},
+    utils, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{budgey_cli::LogOptions, display, output::Output};
+
+pub fn handle_log(
+    context: &BudgeyContext,
+    options: &LogOptions,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let Some(pile) = output.check(operations::get_pile(
+        context,
+        options.pile.as_deref(),
+        budget_io,
+        pile_io,
+    ))?
+    else {
+        return Ok(());
+    };
+
+    if options.graph {
+        let piles = if options.all {
+            operations::get_piles(context, budget_io, pile_io)?
+        } else {
+            vec![pile]
+        };
+        return print_graph(
+            &piles,
+            &current_budget.current_pile_name,
+            options.limit.unwrap_or(usize::MAX),
+            output,
+        );
+    }
+
+    let query = RecordQuery {
+        since: options
+            .since
+            .as_deref()
+            .map(utils::parse_date)
+            .transpose()?
+            .map(utils::start_of_day),
+        until: options
+            .until
+            .as_deref()
+            .map(utils::parse_date)
+            .transpose()?
+            .map(utils::end_of_day),
+        message_contains: options.grep.clone(),
+        author: options.author.clone(),
+        limit: options.limit,
+    };
+    let records = operations::query_records(&pile, &query);
+
+    if !options.oneline {
+        output.println(" --- Current Record ---");
+    }
+    for record in &records {
+        if options.oneline {
+            display::print_record_oneline(record, output);
+        } else {
+            display::print_record(record, output);
+        }
+        if options.show_transactions {
+            for transaction in &record.transactions {
+                display::print_transaction(transaction, output);
+            }
+            if !options.oneline {
+                output.println("|".bold());
+            }
+        }
+    }
+    output.data(json!({ "pile": pile.get_name(), "records": records }))
+}
+
+fn print_graph(
+    piles: &[Pile],
+    focused_pile_name: &str,
+    limit: usize,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let mut records_shown = 0;
+    let mut lines = vec![];
+    for line in log_graph::build_graph(piles) {
+        match &line {
+            GraphLine::Record {
+                lanes,
+                record,
+                pile_names,
+            } => {
+                if records_shown == limit {
+                    break;
+                }
+                records_shown += 1;
+                let labels = if pile_names.is_empty() {
+                    "".to_string()
+                } else {
+                    let names = pile_names
+                        .iter()
+                        .map(|name| {
+                            if name == focused_pile_name {
+                                name.green().bold().to_string()
+                            } else {
+                                name.cyan().to_string()
+                            }
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    format!("({}) ", names)
+                };
+                output.println(format!(
+                    "{} {} {}{} {}",
+                    lanes.bold(),
+                    record.short_id().purple(),
+                    labels,
+                    display::signed_amount(record.amount_after_record, output),
+                    record.message.yellow()
+                ));
+            }
+            GraphLine::Connector(lanes) => {
+                if records_shown == limit {
+                    break;
+                }
+                output.println(lanes.bold())
+            }
+        }
+        lines.push(line);
+    }
+    output.data(json!({ "lines": lines }))
+}
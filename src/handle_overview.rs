@@ -0,0 +1,88 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    utils, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{display, output::Output};
+
+pub fn handle_overview(
+    context: &BudgeyContext,
+    at: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let at_time_stamp = at
+        .map(utils::parse_date)
+        .transpose()?
+        .map(utils::end_of_day);
+
+    match at {
+        Some(at) => output.println(format!(
+            "Budget {} as of {}: ",
+            context.get_current_budget_name(),
+            at
+        )),
+        None => output.println(format!("Budget {}: ", context.get_current_budget_name())),
+    }
+
+    let mut total = 0.0;
+    let mut piles = vec![];
+    for name in &current_budget.pile_names {
+        let pile = pile_io.get_pile(name)?;
+        let sign = if name == &current_budget.current_pile_name {
+            "*".green()
+        } else {
+            "|".white()
+        };
+        match at_time_stamp {
+            Some(time_stamp) => match pile.balance_at(time_stamp) {
+                Some(balance) => {
+                    piles.push(json!({ "name": name, "balance": balance }));
+                    total += balance;
+                    output.println(format!(" {} {}: {}", sign, name, output.amount(balance)));
+                }
+                None => {
+                    piles.push(json!({ "name": name, "balance": null }));
+                    output.println(format!(" {} {}: no records", sign, name))
+                }
+            },
+            None => {
+                total += pile.current_balance;
+                let staged = pile.current_staged_transactions.len();
+                piles.push(json!({
+                    "name": name,
+                    "balance": pile.current_balance,
+                    "staged_transactions": staged,
+                }));
+                if staged > 0 {
+                    output.println(format!(
+                        " {} {}: {} ({} staged transaction(s))",
+                        sign,
+                        name,
+                        output.amount(pile.current_balance),
+                        staged
+                    ));
+                } else {
+                    output.println(format!(
+                        " {} {}: {}",
+                        sign,
+                        name,
+                        output.amount(pile.current_balance)
+                    ));
+                }
+            }
+        }
+    }
+    let total = utils::round_to_two_decimals(total);
+    output.println(format!("Total: {}", display::signed_amount(total, output)));
+    output.data(json!({
+        "budget": context.get_current_budget_name(),
+        "at": at,
+        "piles": piles,
+        "total": total,
+    }))
+}
@@ -1,59 +1,69 @@
-use colored::Colorize;
-
-use crate::{
-    budget_management::update_budget,
-    budgey_cli,
+use budgey::{
     file::{budget_io::BudgetIO, pile_io::PileIO},
-    models::pile::Pile,
-    BudgeyContext,
+    models::{
+        pile::Pile,
+        policy::{PilePolicy, SpendingCap},
+    },
+    operations, utils, BudgeyContext,
 };
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{budgey_cli, output::Output};
 
 pub fn handle_pile_subcommand(
     context: &BudgeyContext,
     subcommand: budgey_cli::PileSubcommand,
     budget_io: &impl BudgetIO,
     pile_io: &impl PileIO,
+    output: &Output,
 ) -> anyhow::Result<()> {
     match subcommand {
         budgey_cli::PileSubcommand::New {
             source,
             new_pile_name,
         } => {
-            let pile = match maybe_get_user_defined_pile(
+            let Some(new_pile) = output.check(operations::create_pile(
                 context,
+                &new_pile_name,
                 source.as_deref(),
                 budget_io,
                 pile_io,
-            )? {
-                Some(source_pile) => source_pile,
-                None => {
-                    println!("Couldn't get the source pile specified");
-                    return Ok(());
-                }
+            ))?
+            else {
+                return Ok(());
             };
-            let initial_balance = pile.current_balance;
-
-            let new_pile = Pile::new_user_created(initial_balance, &new_pile_name, &pile.records);
-            pile_io.create_new_pile(&new_pile)?;
-            let budget = budget_io
-                .get_current_budget(context)?
-                .add_pile(&new_pile_name)
-                .change_current_pile(&new_pile_name);
-            update_budget(&context.get_current_budget_path(), &budget)?;
 
-            println!("Created and checked out new pile: {}", new_pile_name);
+            output.println(format!(
+                "Created and checked out new pile: {}",
+                new_pile_name
+            ));
 
-            Ok(())
+            output.data(json!({
+                "pile": new_pile_name,
+                "balance": new_pile.current_balance,
+            }))
         }
         budgey_cli::PileSubcommand::List => {
             let current_budget = budget_io.get_current_budget(context)?;
             let pile_names = &current_budget.pile_names;
+            output.data(json!({
+                "piles": pile_names
+                    .iter()
+                    .map(|name| json!({
+                        "name": name,
+                        "focused": name == &current_budget.current_pile_name,
+                    }))
+                    .collect::<Vec<_>>(),
+            }))?;
 
             if pile_names.is_empty() {
-                println!("No piles found. \n\nType `budgey pile new <name>` to create a new pile");
+                output.println(
+                    "No piles found. \n\nType `budgey pile new <name>` to create a new pile",
+                );
                 return Ok(());
             };
-            println!("Piles: ");
+            output.println("Piles: ");
 
             for name in pile_names {
                 let sign = if name == &current_budget.current_pile_name {
@@ -62,74 +72,198 @@ pub fn handle_pile_subcommand(
                     "|".white()
                 };
 
-                println!(" {} {}", sign, name);
+                output.println(format!(" {} {}", sign, name));
             }
 
             Ok(())
         }
         budgey_cli::PileSubcommand::Delete { name } => {
-            if name.to_lowercase() == "main" {
-                println!("Cannot delete the main pile of a budget.\n\nIf you want to delete the budget, type `budget delete <name>`");
+            let Some(removed_tags) =
+                output.check(operations::delete_pile(context, &name, budget_io, pile_io))?
+            else {
                 return Ok(());
+            };
+            output.println(format!("Deleted pile: {}", name));
+            if !removed_tags.is_empty() {
+                output.println(format!(
+                    "Removed tags of its records: {}",
+                    removed_tags.join(", ")
+                ));
             }
-            let current_budget = budget_io.get_current_budget(context)?;
-            pile_io.delete_pile(&name, &current_budget)?;
-            let current_budget = budget_io.get_current_budget(context)?.delete_pile(&name);
-            update_budget(&context.get_current_budget_path(), &current_budget)?;
-            println!("Deleted pile: {}", name);
-            Ok(())
+            output.data(json!({ "pile": name, "removed_tags": removed_tags }))
         }
-        budgey_cli::PileSubcommand::Balance { name } => {
-            let get_pile =
-                maybe_get_user_defined_pile(context, name.as_deref(), budget_io, pile_io)?;
-            if let Some(pile) = get_pile {
-                println!(
+        budgey_cli::PileSubcommand::Balance { name, at } => {
+            let Some(pile) = output.check(operations::get_pile(
+                context,
+                name.as_deref(),
+                budget_io,
+                pile_io,
+            ))?
+            else {
+                return Ok(());
+            };
+            let Some(at) = at else {
+                output.println(format!(
                     "Balance of pile {}: {}",
                     pile.get_name(),
-                    pile.current_balance
-                );
-                Ok(())
-            } else {
-                println!("Couldn't get the pile specified");
-                Ok(())
+                    output.amount(pile.current_balance)
+                ));
+                return output.data(json!({
+                    "pile": pile.get_name(),
+                    "balance": pile.current_balance,
+                }));
+            };
+
+            let balance = match utils::parse_date(&at) {
+                Ok(date) => pile.balance_at(utils::end_of_day(date)),
+                Err(_) => {
+                    let Some(record) = output.check(operations::find_record(
+                        context,
+                        Some(&pile.get_name()),
+                        &at,
+                        budget_io,
+                        pile_io,
+                    ))?
+                    else {
+                        return Ok(());
+                    };
+                    Some(record.amount_after_record)
+                }
+            };
+            match balance {
+                Some(balance) => output.println(format!(
+                    "Balance of pile {} at {}: {}",
+                    pile.get_name(),
+                    at,
+                    output.amount(balance)
+                )),
+                None => {
+                    output.println(format!("Pile {} had no records at {}", pile.get_name(), at))
+                }
             }
+            output.data(json!({
+                "pile": pile.get_name(),
+                "at": at,
+                "balance": balance,
+            }))
         }
 
         budgey_cli::PileSubcommand::Focus { name } => {
-            let current_budget = budget_io.get_current_budget(context)?;
-            if !current_budget.pile_names.contains(&name) {
-                println!("Pile doesn't exist in the current budget. Specify another name.");
+            let Some(focused_pile) =
+                output.check(operations::focus_pile(context, &name, budget_io, pile_io))?
+            else {
                 return Ok(());
-            }
-            let new_budget = current_budget.change_current_pile(&name);
-            update_budget(&context.get_current_budget_path(), &new_budget)?;
-            let amount = pile_io.get_current_pile(&new_budget)?.current_balance;
-            let no_current_staged_transactions = pile_io
-                .get_current_pile(&new_budget)?
-                .current_staged_transactions
-                .len();
-            println!("Focused pile: {}\nAmount: {}", name, amount);
+            };
+            let amount = focused_pile.current_balance;
+            let no_current_staged_transactions = focused_pile.current_staged_transactions.len();
+            output.println(format!(
+                "Focused pile: {}\nAmount: {}",
+                name,
+                output.amount(amount)
+            ));
             if no_current_staged_transactions > 0 {
-                println!("Staged transactions: {}", no_current_staged_transactions);
+                output.println(format!(
+                    "Staged transactions: {}",
+                    no_current_staged_transactions
+                ));
             } else {
-                println!("No staged transactions");
+                output.println("No staged transactions");
             }
-            Ok(())
+            output.data(json!({
+                "pile": name,
+                "balance": amount,
+                "staged_transactions": focused_pile.current_staged_transactions,
+            }))
+        }
+        budgey_cli::PileSubcommand::Policy { name, changes } => {
+            let Some(pile) = output.check(operations::get_pile(
+                context,
+                name.as_deref(),
+                budget_io,
+                pile_io,
+            ))?
+            else {
+                return Ok(());
+            };
+            let policy = changed_policy(&pile.policy, &changes);
+            let pile = if policy == pile.policy {
+                pile
+            } else {
+                let result = operations::set_policy(
+                    context,
+                    Some(&pile.get_name()),
+                    &policy,
+                    budget_io,
+                    pile_io,
+                );
+                let Some(pile) = output.check(result)? else {
+                    return Ok(());
+                };
+                output.println(format!(
+                    "Updated the policy of pile {}",
+                    pile.get_name().green()
+                ));
+                pile
+            };
+            print_policy(&pile, output);
+            output.data(json!({ "pile": pile.get_name(), "policy": pile.policy }))
+        }
+        budgey_cli::PileSubcommand::Rebase { onto } => {
+            let Some(rebase) =
+                output.check(operations::rebase_pile(context, &onto, budget_io, pile_io))?
+            else {
+                return Ok(());
+            };
+            output.println(format!(
+                "Rebased pile {} onto {}, replaying {} record(s). Pile now at: {}",
+                rebase.pile.get_name(),
+                onto,
+                rebase.replayed_records,
+                output.amount(rebase.pile.current_balance)
+            ));
+            output.data(json!({
+                "pile": rebase.pile.get_name(),
+                "onto": onto,
+                "replayed_records": rebase.replayed_records,
+                "balance": rebase.pile.current_balance,
+            }))
         }
     }
 }
 
-/// Gets the user defined pile if it exists, or the current pile if not.
-fn maybe_get_user_defined_pile(
-    context: &BudgeyContext,
-    maybe_pile_name: Option<&str>,
-    budget_io: &impl BudgetIO,
-    pile_io: &impl PileIO,
-) -> anyhow::Result<Option<Pile>> {
-    let current_budget = budget_io.get_current_budget(context)?;
-    if let Some(p) = maybe_pile_name {
-        pile_io.maybe_get_pile(p, &current_budget)
-    } else {
-        Ok(Some(pile_io.get_current_pile(&current_budget)?))
+fn changed_policy(policy: &PilePolicy, changes: &budgey_cli::PolicyArgs) -> PilePolicy {
+    let max_withdrawal = match changes.max_withdrawal {
+        Some(amount) => Some(amount),
+        None if changes.no_max_withdrawal => None,
+        None => policy.max_withdrawal,
+    };
+    let spending_cap = match (changes.cap, changes.per) {
+        (Some(amount), Some(period)) => Some(SpendingCap { amount, period }),
+        _ if changes.no_cap => None,
+        _ => policy.spending_cap,
+    };
+    PilePolicy {
+        overdraft: changes.overdraft.unwrap_or(policy.overdraft),
+        max_withdrawal,
+        spending_cap,
     }
 }
+
+fn print_policy(pile: &Pile, output: &Output) {
+    let policy = &pile.policy;
+    output.println(format!("Overdraft: {}", policy.overdraft));
+    output.println(format!(
+        "Limit per withdrawal: {}",
+        policy
+            .max_withdrawal
+            .map(|amount| output.amount(amount))
+            .unwrap_or("none".to_string())
+    ));
+    output.println(format!(
+        "Spending cap: {}",
+        policy
+            .spending_cap
+            .map(|cap| format!("{} per {}", output.amount(cap.amount), cap.period))
+            .unwrap_or("none".to_string())
+    ));
+}
@@ -0,0 +1,50 @@
+use budgey::{journal, BudgeyConfig};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::output::Output;
+
+pub fn handle_reflog(
+    config: &BudgeyConfig,
+    limit: Option<usize>,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let entries = journal::read_entries(config)?
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        output.println("The journal is empty");
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        output.println(format!(
+            "{} {} {} ({} file(s))",
+            format!("@{{{}}}", index).purple(),
+            output.timestamp(&entry.time_stamp),
+            entry.command.yellow(),
+            entry.files.len()
+        ));
+    }
+    output.data(json!({
+        "entries": entries
+            .iter()
+            .map(|entry| json!({
+                "command": entry.command,
+                "time_stamp": entry.time_stamp,
+                "files": entry.files.iter().map(|file| &file.path).collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+pub fn handle_undo(config: &BudgeyConfig, count: usize, output: &Output) -> anyhow::Result<()> {
+    let Some(undone) = output.check(journal::undo(config, count))? else {
+        return Ok(());
+    };
+    for entry in &undone {
+        output.println(format!("Undid: {}", entry.command.yellow()));
+    }
+    output.data(json!({
+        "undone": undone.iter().map(|entry| &entry.command).collect::<Vec<_>>(),
+    }))
+}
@@ -0,0 +1,144 @@
+use budgey::{
+    budgey_state::BudgeyState,
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    remote::{self, PileSync, PileSyncResult},
+    BudgeyConfig, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{budgey_cli::RemoteSubcommand, output::Output};
+
+pub fn handle_remote(
+    context: &BudgeyContext,
+    subcommand: Option<RemoteSubcommand>,
+    budget_io: &impl BudgetIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    match subcommand {
+        Some(RemoteSubcommand::Add { name, path }) => {
+            let Some(path) = output.check(remote::add_remote(context, &name, &path, budget_io))?
+            else {
+                return Ok(());
+            };
+            output.println(format!("Added remote {}: {}", name, path));
+            output.data(json!({ "remote": name, "path": path }))
+        }
+        Some(RemoteSubcommand::Remove { name }) => {
+            if output
+                .check(remote::remove_remote(context, &name, budget_io))?
+                .is_none()
+            {
+                return Ok(());
+            }
+            output.println(format!("Removed remote {}", name));
+            output.data(json!({ "remote": name }))
+        }
+        None => {
+            let budget = budget_io.get_current_budget(context)?;
+            if budget.remotes.is_empty() {
+                output.println("No remotes. Add one with `budgey remote add <name> <path>`");
+            }
+            for (name, path) in &budget.remotes {
+                output.println(format!("{} {}", name.green(), path));
+            }
+            output.data(json!({ "remotes": budget.remotes }))
+        }
+    }
+}
+
+pub fn handle_push(
+    context: &BudgeyContext,
+    remote_name: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let Some(results) = output.check(remote::push(context, remote_name, budget_io, pile_io))?
+    else {
+        return Ok(());
+    };
+    for result in &results {
+        let message = match result.sync {
+            PileSync::Behind { records } => format!(
+                "rejected, the remote has {} record(s) this pile doesn't. Pull first.",
+                records
+            )
+            .red(),
+            PileSync::Diverged => {
+                "rejected, the pile and the remote each have records the other doesn't.".red()
+            }
+            sync => describe_sync(sync).normal(),
+        };
+        print_result(result, message, output);
+    }
+    output.data(json!({ "piles": results }))
+}
+
+pub fn handle_pull(
+    context: &BudgeyContext,
+    remote_name: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let Some(results) = output.check(remote::pull(context, remote_name, budget_io, pile_io))?
+    else {
+        return Ok(());
+    };
+    for result in &results {
+        let message = match result.sync {
+            PileSync::Behind { records } => format!(
+                "the pile has {} record(s) the remote doesn't. Push them.",
+                records
+            )
+            .yellow(),
+            PileSync::Diverged => {
+                "left unchanged, the pile and the remote each have records the other doesn't.".red()
+            }
+            sync => describe_sync(sync).normal(),
+        };
+        print_result(result, message, output);
+    }
+    output.data(json!({ "piles": results }))
+}
+
+pub fn handle_clone(
+    config: &BudgeyConfig,
+    local_state: Option<&BudgeyState>,
+    path: &str,
+    budget_name: Option<&str>,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let Some(budget) =
+        output.check(remote::clone_budget(config, local_state, path, budget_name))?
+    else {
+        return Ok(());
+    };
+    output.println(format!(
+        "Cloned budget {} with {} pile(s) and focused it",
+        budget.budget_detail.budget_name.green(),
+        budget.pile_names.len()
+    ));
+    output.data(json!({
+        "budget": budget.budget_detail.budget_name,
+        "piles": budget.pile_names,
+        "remotes": budget.remotes,
+    }))
+}
+
+fn describe_sync(sync: PileSync) -> String {
+    match sync {
+        PileSync::Created => "created".to_string(),
+        PileSync::FastForwarded { records } => {
+            format!("fast-forwarded {} record(s)", records)
+        }
+        PileSync::UpToDate => "up to date".to_string(),
+        PileSync::Behind { records } => format!("behind by {} record(s)", records),
+        PileSync::Diverged => "diverged".to_string(),
+    }
+}
+
+fn print_result(result: &PileSyncResult, message: impl std::fmt::Display, output: &Output) {
+    output.println(format!(" {} {}: {}", "*".bold(), result.pile_name, message));
+}
@@ -0,0 +1,58 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    models::record_transaction::SHORT_ID_LEN,
+    operations, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{display, output::Output};
+
+pub fn handle_show(
+    context: &BudgeyContext,
+    record_id: &str,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let Some(record) = output.check(operations::find_record(
+        context, None, record_id, budget_io, pile_io,
+    ))?
+    else {
+        return Ok(());
+    };
+    display::print_record_detail(&record, output);
+
+    let mut splits = vec![];
+    for split in record.transactions.iter().filter_map(|t| t.split.as_ref()) {
+        if splits.iter().any(|(id, _)| *id == split.id) {
+            continue;
+        }
+        let piles = operations::get_piles(context, budget_io, pile_io)?;
+        let parts = operations::find_split_parts(&piles, &split.id);
+        output.println(format!("Split payment of {}:", output.amount(split.total)));
+        for part in &parts {
+            let location = match &part.record_id {
+                Some(id) => format!("record {}", &id[..id.len().min(SHORT_ID_LEN)]).purple(),
+                None => "staged".yellow(),
+            };
+            output.println(format!(
+                " {} {}: {} ({})",
+                "*".bold(),
+                part.pile_name,
+                output.amount(part.transaction.amount),
+                location
+            ));
+        }
+        splits.push((
+            split.id.clone(),
+            json!({ "total": split.total, "parts": parts }),
+        ));
+    }
+
+    output.data(json!({
+        "record": record,
+        "amount_before_record": record.amount_before_record(),
+        "splits": splits.into_iter().map(|(_, split)| split).collect::<Vec<_>>(),
+    }))
+}
@@ -0,0 +1,88 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{display, output::Output};
+
+pub fn handle_status(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let current_pile = pile_io.get_current_pile(&current_budget)?;
+
+    output.println(format!(
+        "On budget {}",
+        context.get_current_budget_name().green()
+    ));
+    output.println(format!("On pile {}", current_pile.get_name().green()));
+    if let Some(record) = current_pile.records.last() {
+        output.println(format!(
+            "Last record: {} {}",
+            record.short_id().purple(),
+            record.message.yellow()
+        ));
+    }
+    output.println(format!(
+        "Balance: {} (last committed: {})",
+        output.amount(current_pile.current_balance),
+        output.amount(current_pile.last_committed_balance())
+    ));
+
+    let staged = current_pile.current_staged_transactions.len();
+    if staged > 0 {
+        output.println(format!(
+            "Staged transactions: {} (net change: {})",
+            staged,
+            display::signed_amount(current_pile.staged_balance_change(), output)
+        ));
+        output.println("  (use `budgey commit -m <message>` to record them, or `budgey restore` to discard them)");
+    } else {
+        output.println("No staged transactions");
+    }
+
+    let mut other_staged = vec![];
+    for name in &current_budget.pile_names {
+        if name == &current_budget.current_pile_name {
+            continue;
+        }
+        let pile = pile_io.get_pile(name)?;
+        if !pile.current_staged_transactions.is_empty() {
+            other_staged.push(pile);
+        }
+    }
+    if !other_staged.is_empty() {
+        output.println("Other piles with staged transactions:");
+        for pile in &other_staged {
+            output.println(format!(
+                " - {}: {} staged (net change: {})",
+                pile.get_name(),
+                pile.current_staged_transactions.len(),
+                display::signed_amount(pile.staged_balance_change(), output)
+            ));
+        }
+    }
+
+    output.data(json!({
+        "budget": context.get_current_budget_name(),
+        "pile": current_pile.get_name(),
+        "last_record": current_pile.records.last(),
+        "balance": current_pile.current_balance,
+        "last_committed_balance": current_pile.last_committed_balance(),
+        "staged_transactions": staged,
+        "staged_net_change": current_pile.staged_balance_change(),
+        "other_piles_with_staged_transactions": other_staged
+            .iter()
+            .map(|pile| json!({
+                "name": pile.get_name(),
+                "staged_transactions": pile.current_staged_transactions.len(),
+                "staged_net_change": pile.staged_balance_change(),
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
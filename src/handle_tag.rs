@@ -0,0 +1,78 @@
+use budgey::{
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    models::record_transaction::SHORT_ID_LEN,
+    operations, BudgeyContext,
+};
+use colored::Colorize;
+use serde_json::json;
+
+use crate::{budgey_cli::TagSubcommand, output::Output};
+
+pub fn handle_tag(
+    context: &BudgeyContext,
+    name: Option<String>,
+    record_id: Option<String>,
+    subcommand: Option<TagSubcommand>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    match (subcommand, name) {
+        (Some(TagSubcommand::List), _) | (None, None) => {
+            output.data(json!({ "tags": current_budget.tags }))?;
+            if current_budget.tags.is_empty() {
+                output.println(
+                    "No tags found. \n\nType `budgey tag <name> [record]` to create a new tag.",
+                );
+                return Ok(());
+            }
+            // A tag's record can be on any pile, so it is looked for on the focused pile first.
+            let piles = operations::get_piles_focused_first(context, budget_io, pile_io)?;
+            output.println("Tags: ");
+            for (tag_name, record_id) in &current_budget.tags {
+                let found = piles
+                    .iter()
+                    .find_map(|pile| Some((pile.get_name(), pile.find_record(record_id)?)));
+                let short_id = record_id.get(..SHORT_ID_LEN).unwrap_or(record_id);
+                let detail = match found {
+                    Some((pile_name, record)) => {
+                        format!("{} ({})", record.message.yellow(), pile_name.cyan())
+                    }
+                    None => "(record not found)".red().to_string(),
+                };
+                output.println(format!(
+                    " - {} {} {}",
+                    tag_name.green(),
+                    short_id.purple(),
+                    detail
+                ));
+            }
+            Ok(())
+        }
+        (Some(TagSubcommand::Delete { name }), _) => {
+            if output
+                .check(operations::delete_tag(context, &name, budget_io))?
+                .is_none()
+            {
+                return Ok(());
+            }
+            output.println(format!("Deleted tag: {}", name));
+            output.data(json!({ "tag": name }))
+        }
+        (None, Some(name)) => {
+            let Some(record) = output.check(operations::add_tag(
+                context,
+                &name,
+                record_id.as_deref(),
+                budget_io,
+                pile_io,
+            ))?
+            else {
+                return Ok(());
+            };
+            output.println(format!("Tagged record {} as {}", record.short_id(), name));
+            output.data(json!({ "tag": name, "record_id": record.id }))
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use budgey::{
+    config::Settings,
+    error::BudgeyResult,
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    hooks,
+    models::{
+        pile::Pile,
+        record_transaction::{Record, Transaction, TransactionType},
+    },
+    operations, BudgeyContext,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use crate::output::Output;
+
+const KEY_HELP: &str =
+    "q quit  tab switch pane  ↑/↓ move  enter focus pile  a add  w withdraw  c commit  r restore";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Piles,
+    Records,
+}
+
+/// What the text typed at the prompt is for.
+#[derive(Debug, Clone)]
+enum PromptKind {
+    Amount(TransactionType),
+    Note(TransactionType, f32),
+    CommitMessage,
+    /// Asks before throwing away the staged transactions. Answered with a single key.
+    ConfirmRestore,
+}
+
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            input: String::new(),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self.kind {
+            PromptKind::Amount(TransactionType::Withdraw) => "Amount to withdraw",
+            PromptKind::Amount(_) => "Amount to add",
+            PromptKind::Note(..) => "Note (optional)",
+            PromptKind::CommitMessage => "Commit message",
+            PromptKind::ConfirmRestore => "Throw away the staged transactions? (y/n)",
+        }
+    }
+}
+
+struct App {
+    piles: Vec<Pile>,
+    focused_pile_name: String,
+    pane: Pane,
+    pile_state: ListState,
+    record_state: ListState,
+    prompt: Option<Prompt>,
+    status: String,
+    should_quit: bool,
+    settings: Settings,
+}
+
+impl App {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            piles: vec![],
+            focused_pile_name: String::new(),
+            pane: Pane::Piles,
+            pile_state: ListState::default(),
+            record_state: ListState::default(),
+            prompt: None,
+            status: String::new(),
+            should_quit: false,
+            settings: settings.clone(),
+        }
+    }
+
+    /// Re-reads every pile of the focused budget, keeping the selections in range.
+    fn refresh(
+        &mut self,
+        context: &BudgeyContext,
+        budget_io: &impl BudgetIO,
+        pile_io: &impl PileIO,
+    ) -> anyhow::Result<()> {
+        self.piles = operations::get_piles(context, budget_io, pile_io)?;
+        self.focused_pile_name = budget_io.get_current_budget(context)?.current_pile_name;
+
+        let selected_pile = match self.pile_state.selected() {
+            Some(index) => index.min(self.piles.len().saturating_sub(1)),
+            None => self
+                .piles
+                .iter()
+                .position(|pile| pile.get_name() == self.focused_pile_name)
+                .unwrap_or_default(),
+        };
+        self.pile_state.select(Some(selected_pile));
+        self.clamp_record_selection();
+        Ok(())
+    }
+
+    fn selected_pile(&self) -> Option<&Pile> {
+        self.pile_state
+            .selected()
+            .and_then(|index| self.piles.get(index))
+    }
+
+    fn focused_pile(&self) -> Option<&Pile> {
+        self.piles
+            .iter()
+            .find(|pile| pile.get_name() == self.focused_pile_name)
+    }
+
+    /// The records of the selected pile, newest first.
+    fn records(&self) -> Vec<&Record> {
+        self.selected_pile()
+            .map(|pile| pile.records.iter().rev().collect())
+            .unwrap_or_default()
+    }
+
+    fn selected_record(&self) -> Option<&Record> {
+        self.record_state
+            .selected()
+            .and_then(|index| self.records().get(index).copied())
+    }
+
+    fn clamp_record_selection(&mut self) {
+        let records = self.records().len();
+        let selected = if records == 0 {
+            None
+        } else {
+            Some(
+                self.record_state
+                    .selected()
+                    .unwrap_or_default()
+                    .min(records - 1),
+            )
+        };
+        self.record_state.select(selected);
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let (state, len) = match self.pane {
+            Pane::Piles => (&mut self.pile_state, self.piles.len()),
+            Pane::Records => {
+                let len = self.records().len();
+                (&mut self.record_state, len)
+            }
+        };
+        if len == 0 {
+            return;
+        }
+        let current = state.selected().unwrap_or_default();
+        let next = if down {
+            (current + 1).min(len - 1)
+        } else {
+            current.saturating_sub(1)
+        };
+        state.select(Some(next));
+        if self.pane == Pane::Piles {
+            self.record_state.select(Some(0));
+            self.clamp_record_selection();
+        }
+    }
+
+    /// Shows usage errors in the status line and gives `None`. Any other error is returned.
+    fn check<T>(&mut self, result: BudgeyResult<T>) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.is_usage() => {
+                self.status = e.to_string();
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        code: KeyCode,
+        terminal: &mut DefaultTerminal,
+        context: &BudgeyContext,
+        budget_io: &impl BudgetIO,
+        pile_io: &impl PileIO,
+    ) -> anyhow::Result<()> {
+        if self.prompt.is_some() {
+            return self.handle_prompt_key(code, terminal, context, budget_io, pile_io);
+        }
+        self.status.clear();
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Tab => {
+                self.pane = match self.pane {
+                    Pane::Piles => Pane::Records,
+                    Pane::Records => Pane::Piles,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(false),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(true),
+            KeyCode::Enter if self.pane == Pane::Piles => {
+                let Some(name) = self.selected_pile().map(|pile| pile.get_name()) else {
+                    return Ok(());
+                };
+                let result = operations::focus_pile(context, &name, budget_io, pile_io);
+                if let Some(pile) = self.check(result)? {
+                    self.status = format!("Focused pile: {}", pile.get_name());
+                }
+            }
+            KeyCode::Char('a') => {
+                self.prompt = Some(Prompt::new(PromptKind::Amount(TransactionType::Add)))
+            }
+            KeyCode::Char('w') => {
+                self.prompt = Some(Prompt::new(PromptKind::Amount(TransactionType::Withdraw)))
+            }
+            KeyCode::Char('c') => self.prompt = Some(Prompt::new(PromptKind::CommitMessage)),
+            KeyCode::Char('r') => {
+                let has_staged = self
+                    .focused_pile()
+                    .is_some_and(|pile| !pile.current_staged_transactions.is_empty());
+                if has_staged {
+                    self.prompt = Some(Prompt::new(PromptKind::ConfirmRestore));
+                } else {
+                    self.status = "No staged transactions to restore.".to_string();
+                }
+            }
+            _ => return Ok(()),
+        }
+        self.refresh(context, budget_io, pile_io)
+    }
+
+    fn handle_prompt_key(
+        &mut self,
+        code: KeyCode,
+        terminal: &mut DefaultTerminal,
+        context: &BudgeyContext,
+        budget_io: &impl BudgetIO,
+        pile_io: &impl PileIO,
+    ) -> anyhow::Result<()> {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return Ok(());
+        };
+        if let PromptKind::ConfirmRestore = prompt.kind {
+            self.prompt = None;
+            if !matches!(code, KeyCode::Char('y' | 'Y')) {
+                self.status = "Kept the staged transactions.".to_string();
+                return Ok(());
+            }
+            let pile = operations::restore(context, budget_io, pile_io)?;
+            self.status = format!(
+                "Restored to last record. Pile now at: {}",
+                self.settings.format_amount(pile.current_balance)
+            );
+            return self.refresh(context, budget_io, pile_io);
+        }
+        match code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Enter => {
+                let Some(prompt) = self.prompt.take() else {
+                    return Ok(());
+                };
+                self.submit_prompt(prompt, terminal, context, budget_io, pile_io)?;
+                self.refresh(context, budget_io, pile_io)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn submit_prompt(
+        &mut self,
+        prompt: Prompt,
+        terminal: &mut DefaultTerminal,
+        context: &BudgeyContext,
+        budget_io: &impl BudgetIO,
+        pile_io: &impl PileIO,
+    ) -> anyhow::Result<()> {
+        let input = prompt.input.trim();
+        match prompt.kind {
+            PromptKind::Amount(transaction_type) => {
+                let variables = operations::amount_variables(context, budget_io, pile_io)?;
+                if let Some(amount) = self.check(operations::evaluate_amount(input, &variables))? {
+                    self.prompt = Some(Prompt::new(PromptKind::Note(transaction_type, amount)));
+                }
+            }
+            PromptKind::Note(transaction_type, amount) => {
+                let note = (!input.is_empty()).then_some(input);
+                let transaction = Transaction::new(transaction_type, amount, note);
+                let result = suspended_for_hooks(terminal, context, || {
+                    operations::stage_transaction(context, &transaction, false, budget_io, pile_io)
+                })?;
+                if let Some(pile) = self.check(result)? {
+                    self.status = format!(
+                        "Staged transaction of {}. Pile now at: {}",
+                        self.settings.format_amount(amount),
+                        self.settings.format_amount(pile.current_balance)
+                    );
+                }
+            }
+            PromptKind::ConfirmRestore => {}
+            PromptKind::CommitMessage => {
+                let message = match input {
+                    "" => self.settings.commit_message(&self.focused_pile_name),
+                    input => Some(input.to_string()),
+                };
+                let Some(message) = message else {
+                    self.status = "A commit message is required.".to_string();
+                    return Ok(());
+                };
+                let result = suspended_for_hooks(terminal, context, || {
+                    operations::commit(context, &message, false, budget_io, pile_io)
+                })?;
+                if let Some(record) = self.check(result)? {
+                    self.status = format!(
+                        "Record {} committed. Balance: {}",
+                        record.short_id(),
+                        self.settings.format_amount(record.amount_after_record)
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn handle_tui(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    if !output.is_text() {
+        output.fail("The terminal UI can't be used with --format json.");
+        return Ok(());
+    }
+
+    let mut app = App::new(context.settings());
+    app.refresh(context, budget_io, pile_io)?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut app, context, budget_io, pile_io);
+    ratatui::restore();
+    result
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> anyhow::Result<()> {
+    while !app.should_quit {
+        terminal.draw(|frame| draw(frame, app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key.code, terminal, context, budget_io, pile_io)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs an operation that may run hooks of the focused budget. If it has any, the terminal UI is
+/// left while they run, so anything they write to the terminal doesn't corrupt the screen, and
+/// isn't returned to until enter is pressed, so what they wrote can be read.
+fn suspended_for_hooks<T>(
+    terminal: &mut DefaultTerminal,
+    context: &BudgeyContext,
+    operation: impl FnOnce() -> T,
+) -> anyhow::Result<T> {
+    if !hooks::has_hooks(context) {
+        return Ok(operation());
+    }
+    ratatui::restore();
+    let result = operation();
+    eprint!("Press enter to return to budgey");
+    std::io::stdin().read_line(&mut String::new())?;
+    *terminal = ratatui::init();
+    terminal.clear()?;
+    Ok(result)
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [main, chain, footer] = Layout::vertical([
+        Constraint::Min(8),
+        Constraint::Length(8),
+        Constraint::Length(3),
+    ])
+    .areas(frame.area());
+    let [piles, records, detail] = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(40),
+        Constraint::Percentage(35),
+    ])
+    .areas(main);
+
+    let pile_items = app
+        .piles
+        .iter()
+        .map(|pile| {
+            let focused = pile.get_name() == app.focused_pile_name;
+            let mut spans = vec![
+                Span::raw(if focused { "* " } else { "  " }),
+                Span::raw(pile.get_name()).bold(),
+                Span::raw(format!(
+                    "  {}",
+                    app.settings.format_amount(pile.current_balance)
+                )),
+            ];
+            if !pile.current_staged_transactions.is_empty() {
+                spans
+                    .push(format!(" ({} staged)", pile.current_staged_transactions.len()).yellow());
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect::<Vec<ListItem>>();
+    frame.render_stateful_widget(
+        List::new(pile_items)
+            .block(pane_block("Piles", app.pane == Pane::Piles))
+            .highlight_style(highlight_style()),
+        piles,
+        &mut app.pile_state,
+    );
+
+    let record_items = app
+        .records()
+        .into_iter()
+        .map(|record| {
+            ListItem::new(Line::from(vec![
+                Span::raw(record.short_id().to_string()).magenta(),
+                Span::raw(" "),
+                signed_span(record.balance_change()),
+                Span::raw(" "),
+                Span::raw(record.message.clone()).yellow(),
+            ]))
+        })
+        .collect::<Vec<ListItem>>();
+    let records_title = match app.selected_pile() {
+        Some(pile) => format!("Records of {}", pile.get_name()),
+        None => "Records".to_string(),
+    };
+    frame.render_stateful_widget(
+        List::new(record_items)
+            .block(pane_block(&records_title, app.pane == Pane::Records))
+            .highlight_style(highlight_style()),
+        records,
+        &mut app.record_state,
+    );
+
+    let detail_lines = match app.selected_record() {
+        Some(record) => record_detail_lines(record, &app.settings),
+        None => vec![Line::raw("No record selected")],
+    };
+    frame.render_widget(
+        Paragraph::new(detail_lines)
+            .block(pane_block("Record", false))
+            .wrap(Wrap { trim: false }),
+        detail,
+    );
+
+    let (chain_title, chain_lines) = match app.focused_pile() {
+        Some(pile) => (
+            format!(
+                "Staged on {} (balance {}, last committed {})",
+                pile.get_name(),
+                app.settings.format_amount(pile.current_balance),
+                app.settings.format_amount(pile.last_committed_balance())
+            ),
+            if pile.current_staged_transactions.is_empty() {
+                vec![Line::raw("No staged transactions")]
+            } else {
+                pile.current_staged_transactions
+                    .iter()
+                    .rev()
+                    .map(transaction_line)
+                    .collect()
+            },
+        ),
+        None => ("Staged".to_string(), vec![]),
+    };
+    frame.render_widget(
+        Paragraph::new(chain_lines).block(pane_block(&chain_title, false)),
+        chain,
+    );
+
+    let footer_line = match &app.prompt {
+        Some(prompt) => Line::from(vec![
+            Span::raw(format!("{}: ", prompt.label())).bold(),
+            Span::raw(prompt.input.clone()),
+            Span::raw("_").add_modifier(Modifier::SLOW_BLINK),
+        ]),
+        None if !app.status.is_empty() => Line::raw(app.status.clone()),
+        None => Line::raw(KEY_HELP).dim(),
+    };
+    frame.render_widget(Paragraph::new(footer_line).block(Block::bordered()), footer);
+}
+
+fn pane_block(title: &str, active: bool) -> Block<'static> {
+    let block = Block::bordered().title(title.to_string());
+    if active {
+        block.border_style(Style::new().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+fn highlight_style() -> Style {
+    Style::new().add_modifier(Modifier::REVERSED)
+}
+
+/// An amount in green with a leading '+' if positive, red otherwise.
+fn signed_span(amount: f32) -> Span<'static> {
+    if amount > 0.0 {
+        Span::raw(format!("+{}", amount)).green()
+    } else {
+        Span::raw(format!("{}", amount)).red()
+    }
+}
+
+fn transaction_line(transaction: &Transaction) -> Line<'static> {
+    let sign = match transaction.transaction_type {
+        TransactionType::Add => Span::raw("+").green(),
+        TransactionType::Withdraw => Span::raw("-").red(),
+        TransactionType::Init => Span::raw("~"),
+    };
+    Line::from(vec![
+        Span::raw("* "),
+        sign,
+        Span::raw(transaction.amount.to_string()),
+        Span::raw("    "),
+        Span::raw(transaction.note.clone().unwrap_or_default()).yellow(),
+        Span::raw(
+            transaction
+                .date
+                .map(|date| format!(" (dated {})", date.format("%Y-%m-%d")))
+                .unwrap_or_default(),
+        )
+        .cyan(),
+    ])
+}
+
+fn record_detail_lines(record: &Record, settings: &Settings) -> Vec<Line<'static>> {
+    let mut lines = vec![
+        Line::from(vec![
+            Span::raw("record ").bold(),
+            Span::raw(record.id.clone()).magenta(),
+        ]),
+        Line::raw(format!(
+            "Date:    {}",
+            settings.format_timestamp(&record.time_stamp)
+        )),
+        Line::from(vec![
+            Span::raw("Message: "),
+            Span::raw(record.message.clone()).yellow(),
+        ]),
+    ];
+    if let Some(source) = &record.cherry_picked_from {
+        lines.push(Line::raw(format!("Cherry-picked from: {}", source)));
+    }
+    lines.push(Line::raw(format!(
+        "Balance before: {}",
+        settings.format_amount(record.amount_before_record())
+    )));
+    lines.push(Line::raw(format!(
+        "Balance after:  {}",
+        settings.format_amount(record.amount_after_record)
+    )));
+    lines.push(Line::raw("Transactions:"));
+    lines.extend(record.transactions.iter().map(transaction_line));
+    lines
+}
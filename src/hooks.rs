@@ -0,0 +1,178 @@
+//! Git-style hooks.
+//!
+//! A hook is an executable named after the hook in the `hooks` folder of a budget's directory.
+//! It runs in the budget's directory and receives the pile, and the record or transaction
+//! involved, as JSON on stdin. Hooks that run before an operation can veto it by exiting with a
+//! non-zero status. What a hook writes is shown on stderr, like Git, so it can explain a veto
+//! without mixing into budgey's own output.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::{BudgeyError, BudgeyResult},
+    models::{
+        pile::Pile,
+        record_transaction::{Record, Transaction},
+    },
+    utils::concat_paths,
+    BudgeyContext,
+};
+
+/// The points at which hooks are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Before staged transactions are committed to a record.
+    PreCommit,
+    /// After a record has been committed. Can't veto the commit.
+    PostCommit,
+    /// Before a transaction adding to a pile is staged.
+    PreAdd,
+    /// Before a transaction withdrawing from a pile is staged.
+    PreWithdraw,
+    /// Before a pile is deleted.
+    PrePileDelete,
+}
+
+impl Hook {
+    /// The file name of the hook in the hooks folder.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::PreCommit => "pre-commit",
+            Hook::PostCommit => "post-commit",
+            Hook::PreAdd => "pre-add",
+            Hook::PreWithdraw => "pre-withdraw",
+            Hook::PrePileDelete => "pre-pile-delete",
+        }
+    }
+
+    /// Whether a failing hook stops the operation.
+    pub fn can_veto(&self) -> bool {
+        *self != Hook::PostCommit
+    }
+}
+
+/// What a hook is told about the operation it runs around.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HookInput<'a> {
+    /// The pile as it will be after the operation, or as it is for a pile about to be deleted.
+    pub pile: &'a Pile,
+    pub record: Option<&'a Record>,
+    pub transaction: Option<&'a Transaction>,
+}
+
+impl<'a> HookInput<'a> {
+    pub fn new(pile: &'a Pile) -> Self {
+        Self {
+            pile,
+            record: None,
+            transaction: None,
+        }
+    }
+
+    pub fn with_record(self, record: &'a Record) -> Self {
+        Self {
+            record: Some(record),
+            ..self
+        }
+    }
+
+    pub fn with_transaction(self, transaction: &'a Transaction) -> Self {
+        Self {
+            transaction: Some(transaction),
+            ..self
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HookDocument<'a> {
+    hook: &'static str,
+    budget: String,
+    #[serde(flatten)]
+    input: HookInput<'a>,
+}
+
+/// The name of the folder in a budget's folder that holds its hooks.
+pub const HOOKS_FOLDER_NAME: &str = "hooks";
+
+/// The folder holding the hooks of the focused budget.
+pub fn hooks_path(context: &BudgeyContext) -> String {
+    concat_paths(&context.get_current_budget_path(), HOOKS_FOLDER_NAME)
+}
+
+/// Whether the focused budget has any hooks that would be run.
+pub fn has_hooks(context: &BudgeyContext) -> bool {
+    std::fs::read_dir(hooks_path(context))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|entry| is_executable(&entry.path()))
+}
+
+/// Runs the hook of the focused budget, if there is one.
+///
+/// A hook that can veto and exits with a non-zero status gives [`BudgeyError::HookRejected`] with
+/// its exit status. Failures of other hooks are ignored.
+pub fn run_hook(context: &BudgeyContext, hook: Hook, input: HookInput) -> BudgeyResult<()> {
+    let path = concat_paths(&hooks_path(context), hook.name());
+    if !is_executable(Path::new(&path)) {
+        return Ok(());
+    }
+    let document = HookDocument {
+        hook: hook.name(),
+        budget: context.get_current_budget_name(),
+        input,
+    };
+    let result = execute(
+        &path,
+        &context.get_current_budget_path(),
+        &serde_json::to_vec(&document).map_err(anyhow::Error::from)?,
+    );
+    if !hook.can_veto() {
+        return Ok(());
+    }
+
+    let status = result?;
+    if status.success() {
+        return Ok(());
+    }
+    Err(BudgeyError::HookRejected(
+        hook.name().to_string(),
+        status.to_string(),
+    ))
+}
+
+fn execute(path: &str, budget_path: &str, stdin: &[u8]) -> std::io::Result<ExitStatus> {
+    let mut child = Command::new(path)
+        .current_dir(budget_path)
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    if let Some(mut child_stdin) = child.stdin.take() {
+        // A hook doesn't have to read its input, so it may have closed stdin already.
+        match child_stdin.write_all(stdin) {
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,390 @@
+//! The undo journal.
+//!
+//! Before a command that changes files runs, the files in the budgey root folder and the folders
+//! of the budgets it works on are read. Afterwards, the files that changed are written to the
+//! journal along with their contents from before, so the command can be rolled back later.
+//! Budgets created by the command are read afterwards too, so they can be removed again.
+//!
+//! Encrypted budgets are kept out of the journal: only digests of their files are read, so no
+//! plain copy of them or of the commands that changed them is ever written.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::ErrorKind,
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    encryption,
+    error::{BudgeyError, BudgeyResult},
+    utils::{self, concat_paths},
+    BudgeyConfig,
+};
+
+pub const JOURNAL_FILE_NAME: &str = "journal.json";
+
+/// The most entries kept in the journal. Older entries are dropped.
+const MAX_ENTRIES: usize = 100;
+
+/// The contents of the files a command may change, keyed by path relative to the root.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    files: BTreeMap<String, FileState>,
+    /// The budgets whose folders were read.
+    budget_names: BTreeSet<String>,
+    /// The folders of the budgets that were encrypted.
+    encrypted_budgets: BTreeSet<String>,
+    /// Every folder in the root, to tell which budgets the command creates.
+    folders: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FileState {
+    Plain(String),
+    /// The digest of a file of an encrypted budget.
+    Encrypted(String),
+}
+
+/// A command that changed files, with what they held before it ran.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub command: String,
+    #[serde(deserialize_with = "utils::deserialize_time_stamp")]
+    pub time_stamp: DateTime<Utc>,
+    pub files: Vec<FileImage>,
+}
+
+/// A file's contents before a command changed it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileImage {
+    /// The path of the file relative to the budgey root folder.
+    pub path: String,
+    /// The contents before the command, or None if the command created the file.
+    pub before: Option<String>,
+}
+
+/// Reads the files in the budgey root folder, except the journal itself, and the files in the
+/// folders of the given budgets.
+pub fn snapshot(config: &BudgeyConfig, budget_names: &[String]) -> anyhow::Result<Snapshot> {
+    let (mut files, folders) = read_root(config)?;
+    let mut encrypted_budgets = BTreeSet::new();
+    for budget_name in budget_names {
+        let budget_path = config.get_budget_path(budget_name);
+        let encrypted = encryption::is_encrypted(&budget_path);
+        if encrypted {
+            encrypted_budgets.insert(budget_name.to_string());
+        }
+        for (path, contents) in utils::read_files_recursively(Path::new(&budget_path))? {
+            let state = if encrypted {
+                FileState::Encrypted(hex::encode(Sha256::digest(&contents)))
+            } else {
+                // Anything that isn't text, such as a compiled hook, isn't budgey's data.
+                match String::from_utf8(contents) {
+                    Ok(contents) => FileState::Plain(contents),
+                    Err(_) => continue,
+                }
+            };
+            files.insert(concat_paths(budget_name, &path), state);
+        }
+    }
+    Ok(Snapshot {
+        files,
+        budget_names: budget_names.iter().cloned().collect(),
+        encrypted_budgets,
+        folders,
+    })
+}
+
+/// Journals the files changed since the snapshot was taken, if any, returning the new entry.
+/// Nothing is journaled if the command changed a budget that was or is now encrypted.
+pub fn record(
+    config: &BudgeyConfig,
+    command: &str,
+    before: &Snapshot,
+) -> anyhow::Result<Option<JournalEntry>> {
+    let (_, folders) = read_root(config)?;
+    let budget_names = before
+        .budget_names
+        .iter()
+        .chain(folders.difference(&before.folders))
+        .cloned()
+        .collect::<Vec<String>>();
+    let after = snapshot(config, &budget_names)?;
+    let paths = before
+        .files
+        .keys()
+        .chain(after.files.keys())
+        .collect::<BTreeSet<&String>>();
+    let changed = paths
+        .into_iter()
+        .filter(|path| before.files.get(*path) != after.files.get(*path))
+        .collect::<Vec<&String>>();
+    if changed.iter().any(|path| {
+        let folder = budget_folder(path);
+        before.encrypted_budgets.contains(folder) || after.encrypted_budgets.contains(folder)
+    }) {
+        return Ok(None);
+    }
+    let files = changed
+        .into_iter()
+        .filter_map(|path| {
+            let before = match before.files.get(path) {
+                Some(FileState::Plain(contents)) => Some(contents.to_string()),
+                Some(FileState::Encrypted(_)) => return None,
+                None => None,
+            };
+            Some(FileImage {
+                path: path.to_string(),
+                before,
+            })
+        })
+        .collect::<Vec<FileImage>>();
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let entry = JournalEntry {
+        command: command.to_string(),
+        time_stamp: Utc::now(),
+        files,
+    };
+    let mut entries = read_entries(config)?;
+    entries.insert(0, entry.clone());
+    entries.truncate(MAX_ENTRIES);
+    write_entries(config, &entries)?;
+    Ok(Some(entry))
+}
+
+/// Drops every entry that changed the given budget's files, so none of its contents are left in
+/// the journal once it is encrypted.
+pub fn forget_budget(config: &BudgeyConfig, budget_name: &str) -> anyhow::Result<()> {
+    let entries = read_entries(config)?;
+    let kept = entries
+        .iter()
+        .filter(|entry| {
+            !entry
+                .files
+                .iter()
+                .any(|file| budget_folder(&file.path) == budget_name)
+        })
+        .cloned()
+        .collect::<Vec<JournalEntry>>();
+    if kept.len() != entries.len() {
+        write_entries(config, &kept)?;
+    }
+    Ok(())
+}
+
+/// Every entry in the journal, newest first.
+pub fn read_entries(config: &BudgeyConfig) -> anyhow::Result<Vec<JournalEntry>> {
+    match fs::read_to_string(journal_path(config)) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Rolls back the newest entries of the journal, newest first, and removes them from it.
+pub fn undo(config: &BudgeyConfig, count: usize) -> BudgeyResult<Vec<JournalEntry>> {
+    let mut entries = read_entries(config)?;
+    if count == 0 || count > entries.len() {
+        return Err(BudgeyError::NotEnoughJournalEntries(count, entries.len()));
+    }
+    let undone = entries.drain(..count).collect::<Vec<JournalEntry>>();
+    for entry in &undone {
+        let encrypted = entry
+            .files
+            .iter()
+            .map(|file| budget_folder(&file.path))
+            .find(|folder| encryption::is_encrypted(&config.get_budget_path(folder)));
+        if let Some(folder) = encrypted {
+            return Err(BudgeyError::EncryptedJournalEntry(
+                entry.command.to_string(),
+                folder.to_string(),
+            ));
+        }
+    }
+    for entry in &undone {
+        for file in &entry.files {
+            restore_file(config, file)?;
+        }
+    }
+    write_entries(config, &entries)?;
+    Ok(undone)
+}
+
+fn restore_file(config: &BudgeyConfig, file: &FileImage) -> std::io::Result<()> {
+    let root = Path::new(&config.root_path);
+    let path = root.join(&file.path);
+    match &file.before {
+        Some(contents) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, contents)
+        }
+        None => {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            // Remove the folders the command created along with the file.
+            let mut parent = path.parent();
+            while let Some(directory) = parent.filter(|directory| *directory != root) {
+                if fs::remove_dir(directory).is_err() {
+                    break;
+                }
+                parent = directory.parent();
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The text files directly in the root folder, except the journal, and the names of the folders
+/// in it.
+fn read_root(
+    config: &BudgeyConfig,
+) -> std::io::Result<(BTreeMap<String, FileState>, BTreeSet<String>)> {
+    let mut files = BTreeMap::new();
+    let mut folders = BTreeSet::new();
+    let entries = match fs::read_dir(&config.root_path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((files, folders)),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() {
+            folders.insert(name);
+        } else if name != JOURNAL_FILE_NAME {
+            if let Ok(contents) = String::from_utf8(fs::read(entry.path())?) {
+                files.insert(name, FileState::Plain(contents));
+            }
+        }
+    }
+    Ok((files, folders))
+}
+
+/// The top folder of a path relative to the root, which is a budget's folder for budget files.
+fn budget_folder(path: &str) -> &str {
+    path.split('/').next().unwrap_or(path)
+}
+
+fn journal_path(config: &BudgeyConfig) -> String {
+    concat_paths(&config.root_path, JOURNAL_FILE_NAME)
+}
+
+fn write_entries(config: &BudgeyConfig, entries: &[JournalEntry]) -> anyhow::Result<()> {
+    fs::write(journal_path(config), serde_json::to_string(entries)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::record_transaction::{Transaction, TransactionType},
+        operations,
+        test_utils::TestBudgey,
+    };
+
+    #[test]
+    fn test_only_the_budgets_worked_on_are_journaled() {
+        let budgey = TestBudgey::new("home");
+        let (root, config) = (budgey.root(), budgey.config());
+        fs::create_dir_all(root.join("work")).unwrap();
+        fs::write(root.join("work/main.json"), "[]").unwrap();
+        let state = fs::read_to_string(root.join("budgey_state.json")).unwrap();
+
+        let before = snapshot(config, &["home".to_string()]).unwrap();
+        assert!(before.files.contains_key("home/main/main.json"));
+        assert!(!before.files.contains_key("work/main.json"));
+        fs::write(root.join("work/main.json"), "[1]").unwrap();
+        fs::create_dir_all(root.join("new")).unwrap();
+        fs::write(root.join("new/new.json"), "{}").unwrap();
+        fs::write(root.join("budgey_state.json"), "new").unwrap();
+        let entry = record(config, "budget new new", &before).unwrap().unwrap();
+        let paths = entry
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file.before.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("budgey_state.json", Some(state.as_str())),
+                ("new/new.json", None)
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn test_encrypted_budgets_are_kept_out_of_the_journal() {
+        let budgey = TestBudgey::new("home");
+        let config = budgey.config();
+        let pile_path = budgey.root().join("home/main/main.json");
+        let budgets = vec!["home".to_string()];
+
+        let before = snapshot(config, &budgets).unwrap();
+        fs::write(&pile_path, "salary and rent").unwrap();
+        assert!(record(config, "commit -m rent", &before).unwrap().is_some());
+
+        let before = snapshot(config, &budgets).unwrap();
+        fs::write(
+            budgey.root().join("home").join(encryption::KEY_FILE_NAME),
+            "{}",
+        )
+        .unwrap();
+        fs::write(&pile_path, "ciphertext").unwrap();
+        assert!(record(config, "encrypt", &before).unwrap().is_none());
+        assert!(matches!(
+            undo(config, 1),
+            Err(BudgeyError::EncryptedJournalEntry(..))
+        ));
+
+        forget_budget(config, "home").unwrap();
+        assert!(read_entries(config).unwrap().is_empty());
+        let before = snapshot(config, &budgets).unwrap();
+        fs::write(&pile_path, "more ciphertext").unwrap();
+        assert!(record(config, "commit -m secret", &before)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_undo_restores_piles_as_they_were_before_the_command() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        let deposit = Transaction::new(TransactionType::Add, 20.0, None);
+        operations::stage_transaction(context, &deposit, false, budget_io, pile_io).unwrap();
+        let staged = operations::get_pile(context, None, budget_io, pile_io).unwrap();
+
+        let before = snapshot(budgey.config(), &["home".to_string()]).unwrap();
+        operations::commit(context, "gift", false, budget_io, pile_io).unwrap();
+        record(budgey.config(), "commit -m gift", &before)
+            .unwrap()
+            .unwrap();
+        let committed = operations::get_pile(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(staged.records.len() + 1, committed.records.len());
+
+        let undone = undo(budgey.config(), 1).unwrap();
+        assert_eq!("commit -m gift", undone[0].command);
+        let restored = operations::get_pile(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(staged.records.len(), restored.records.len());
+        assert_eq!(staged.current_balance, restored.current_balance);
+        assert_eq!(1, restored.current_staged_transactions.len());
+        assert!(read_entries(budgey.config()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+//! Budgey - a budgeting library in the style of Git.
+//!
+//! Budgets hold piles, piles hold a history of records, and records are made by committing staged
+//! transactions. The [`operations`] module exposes these as typed operations that return results
+//! rather than printing, and the `budgey` binary is a thin command line front end over them.
+
+use budgey_state::BudgeyState;
+use config::Settings;
+use utils::{concat_paths, create_json_file_name};
+
+pub mod allocation;
+pub mod budget_management;
+pub mod budgey_state;
+pub mod bundle;
+pub mod config;
+pub mod encryption;
+pub mod error;
+pub mod file;
+pub mod hooks;
+pub mod journal;
+pub mod log_graph;
+pub mod models;
+pub mod operations;
+pub mod remote;
+#[cfg(test)]
+mod test_utils;
+pub mod utils;
+
+#[derive(Debug, Clone)]
+pub struct BudgeyContext {
+    config: BudgeyConfig,
+    state: BudgeyState,
+    settings: Settings,
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgeyConfig {
+    root_path: String,
+    state_json_name: String,
+}
+
+impl BudgeyConfig {
+    pub fn new(budgey_path: &str, state_json_name: &str) -> Self {
+        Self {
+            root_path: budgey_path.to_string(),
+            state_json_name: state_json_name.to_string(),
+        }
+    }
+    pub fn get_state_path(&self) -> String {
+        concat_paths(&self.root_path, &self.state_json_name)
+    }
+    pub fn get_budget_path(&self, budget_name: &str) -> String {
+        concat_paths(&self.root_path, budget_name)
+    }
+}
+
+impl BudgeyContext {
+    pub fn new(state: &BudgeyState, budgey_config: &BudgeyConfig) -> Self {
+        Self {
+            config: budgey_config.clone(),
+            state: state.clone(),
+            settings: Settings::default(),
+        }
+    }
+    pub fn with_settings(&self, settings: &Settings) -> Self {
+        Self {
+            settings: settings.clone(),
+            ..self.clone()
+        }
+    }
+    pub fn get_current_budget_path(&self) -> String {
+        let current_budget = &self.state.current_focused_budget_name;
+        let budgey_path = &self.config.root_path;
+        concat_paths(budgey_path, current_budget)
+    }
+    pub fn get_current_budget_name(&self) -> String {
+        self.state.current_focused_budget_name.clone()
+    }
+    pub fn get_current_budget_json_path(&self) -> String {
+        concat_paths(
+            &self.get_current_budget_path(),
+            &create_json_file_name(&self.state.current_focused_budget_name),
+        )
+    }
+
+    pub fn state(&self) -> &BudgeyState {
+        &self.state
+    }
+    pub fn config(&self) -> &BudgeyConfig {
+        &self.config
+    }
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn update_state(&self, new_state: &BudgeyState) -> Self {
+        Self::new(new_state, &self.config).with_settings(&self.settings)
+    }
+    pub fn contains_budget(&self, budget_name: &str) -> bool {
+        self.state
+            .budget_names
+            .iter()
+            .any(|name| name == budget_name)
+    }
+}
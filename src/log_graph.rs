@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::models::{pile::Pile, record_transaction::Record};
+
+/// A line of the record graph shown by `log --graph`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphLine {
+    /// A record, drawn with the lanes that are active beside it.
+    Record {
+        lanes: String,
+        record: Record,
+        pile_names: Vec<String>,
+    },
+    /// A line joining a pile's lane back into the lane of the record it was forked from.
+    Connector(String),
+}
+
+struct Node {
+    record: Record,
+    parent: Option<usize>,
+    depth: usize,
+}
+
+/// Builds the record graph of the given piles, newest records first.
+///
+/// Piles share the records they were created from, so every record that appears in several piles
+/// with the same history is drawn once, and each pile gets its own lane from the point it forked.
+pub fn build_graph(piles: &[Pile]) -> Vec<GraphLine> {
+    let mut nodes: Vec<Node> = vec![];
+    let mut keys: HashMap<(Option<usize>, String), usize> = HashMap::new();
+    let mut heads: HashMap<usize, Vec<String>> = HashMap::new();
+
+    for pile in piles {
+        let mut parent = None;
+        for (depth, record) in pile.records.iter().enumerate() {
+            let key = *keys.entry((parent, record.id.clone())).or_insert_with(|| {
+                nodes.push(Node {
+                    record: record.clone(),
+                    parent,
+                    depth,
+                });
+                nodes.len() - 1
+            });
+            parent = Some(key);
+        }
+        if let Some(head) = parent {
+            heads.entry(head).or_default().push(pile.get_name());
+        }
+    }
+
+    let mut pending_children = vec![0; nodes.len()];
+    for node in &nodes {
+        if let Some(parent) = node.parent {
+            pending_children[parent] += 1;
+        }
+    }
+    let mut ready = (0..nodes.len())
+        .filter(|key| pending_children[*key] == 0)
+        .collect::<Vec<usize>>();
+
+    let mut lines = vec![];
+    let mut lanes: Vec<Option<usize>> = vec![];
+
+    while !ready.is_empty() {
+        let (ready_index, _) = ready
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, key)| {
+                let node = &nodes[**key];
+                (node.record.time_stamp, node.depth)
+            })
+            .expect("Ready nodes should not be empty");
+        let key = ready.swap_remove(ready_index);
+        let node = &nodes[key];
+
+        let matching = (0..lanes.len())
+            .filter(|lane| lanes[*lane] == Some(key))
+            .collect::<Vec<usize>>();
+        if let [column, forked @ ..] = matching.as_slice() {
+            if !forked.is_empty() {
+                let mut connector = vec![' '; lanes.len() * 2];
+                for (lane, expected) in lanes.iter().enumerate() {
+                    if lane == *column || (expected.is_some() && !forked.contains(&lane)) {
+                        connector[lane * 2] = '|';
+                    }
+                }
+                for lane in forked {
+                    connector[lane * 2 - 1] = '/';
+                    lanes[*lane] = None;
+                }
+                lines.push(GraphLine::Connector(
+                    connector.into_iter().collect::<String>().trim_end().into(),
+                ));
+            }
+        }
+        let column = match matching.first() {
+            Some(column) => *column,
+            None => match lanes.iter().position(|lane| lane.is_none()) {
+                Some(free) => free,
+                None => {
+                    lanes.push(None);
+                    lanes.len() - 1
+                }
+            },
+        };
+        lanes[column] = Some(key);
+
+        let row = (0..lanes.len())
+            .map(|lane| {
+                if lane == column {
+                    "*"
+                } else if lanes[lane].is_some() {
+                    "|"
+                } else {
+                    " "
+                }
+            })
+            .collect::<Vec<&str>>()
+            .join(" ");
+        lines.push(GraphLine::Record {
+            lanes: row.trim_end().to_string(),
+            record: node.record.clone(),
+            pile_names: heads.remove(&key).unwrap_or_default(),
+        });
+
+        lanes[column] = node.parent;
+        while lanes.last().is_some_and(|lane| lane.is_none()) {
+            lanes.pop();
+        }
+
+        if let Some(parent) = node.parent {
+            pending_children[parent] -= 1;
+            if pending_children[parent] == 0 {
+                ready.push(parent);
+            }
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::record_transaction::{Transaction, TransactionType},
+        test_utils::commit,
+    };
+
+    #[test]
+    fn test_build_graph_shows_fork() {
+        let add = || Transaction::new(TransactionType::Add, 1.0, None);
+        let at = |seconds| chrono::DateTime::from_timestamp(seconds, 0).unwrap();
+        let main = commit(Pile::default_main_pile(), "first", add(), at(1));
+        let side = Pile::new_user_created(main.current_balance, "side", &main.records);
+        let side = commit(side, "side", add(), at(3));
+        let main = commit(main, "second", add(), at(2));
+
+        let rendered = build_graph(&[main, side])
+            .into_iter()
+            .map(|line| match line {
+                GraphLine::Record {
+                    lanes,
+                    record,
+                    pile_names,
+                } => format!("{} {} {:?}", lanes, record.message, pile_names),
+                GraphLine::Connector(lanes) => lanes,
+            })
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            vec![
+                "* side [\"side\"]",
+                "| * second [\"main\"]",
+                "|/",
+                "* first []",
+                "* Initialised main []",
+            ],
+            rendered
+        );
+    }
+}
@@ -1,88 +1,49 @@
-use crate::{
-    budgey_state::BudgeyState,
-    models::record_transaction::{Record, Transaction, TransactionType},
-    utils::round_to_two_decimals,
+use std::collections::BTreeMap;
+
+use budgey::{
+    config::{self, Settings},
+    file::{
+        budget_io::{BudgetIO, BudgetIOImpl},
+        pile_io::{PileIO, PileIOImpl},
+        state_io::{StateIO, StateIOImpl},
+    },
+    journal,
+    models::{
+        pile::Pile,
+        policy::PolicyViolation,
+        record_transaction::{Transaction, TransactionType},
+    },
+    operations,
+    utils::{self, create_json_file_name},
+    BudgeyConfig, BudgeyContext,
 };
-use anyhow::anyhow;
-use budgey_cli::Commands;
-use clap::Parser;
+use budgey_cli::{BudgeyCLI, BudgeyCommand, Commands, TransactionArgs};
+use clap::{CommandFactory, Parser};
 use colored::Colorize;
-use file::{
-    budget_io::{BudgetIO, BudgetIOImpl},
-    pile_io::{PileIO, PileIOImpl},
-    state_io::{StateIO, StateIOImpl},
-};
-use utils::{concat_paths, create_json_file_name};
+use output::Output;
+use serde_json::json;
 
-mod budget_management;
 mod budgey_cli;
-mod budgey_state;
-mod file;
+mod display;
+mod handle_allocation;
 mod handle_budget;
+mod handle_bundle;
+mod handle_cherry_pick;
+mod handle_config;
+mod handle_diff;
+mod handle_encryption;
+mod handle_goal;
 mod handle_init;
+mod handle_log;
+mod handle_overview;
 mod handle_pile;
-mod models;
-mod utils;
-
-#[derive(Debug, Clone)]
-pub struct BudgeyContext {
-    config: BudgeyConfig,
-    state: BudgeyState,
-}
-
-#[derive(Debug, Clone)]
-pub struct BudgeyConfig {
-    root_path: String,
-    state_json_name: String,
-}
-
-impl BudgeyConfig {
-    pub fn new(budgey_path: &str, state_json_name: &str) -> Self {
-        Self {
-            root_path: budgey_path.to_string(),
-            state_json_name: state_json_name.to_string(),
-        }
-    }
-    pub fn get_state_path(&self) -> String {
-        concat_paths(&self.root_path, &self.state_json_name)
-    }
-    pub fn get_budget_path(&self, budget_name: &str) -> String {
-        concat_paths(&self.root_path, budget_name)
-    }
-}
-
-impl BudgeyContext {
-    pub fn new(state: &BudgeyState, budgey_config: &BudgeyConfig) -> Self {
-        Self {
-            config: budgey_config.clone(),
-            state: state.clone(),
-        }
-    }
-    pub fn get_current_budget_path(&self) -> String {
-        let current_budget = &self.state.current_focused_budget_name;
-        let budgey_path = &self.config.root_path;
-        concat_paths(budgey_path, current_budget)
-    }
-    pub fn get_current_budget_name(&self) -> String {
-        self.state.current_focused_budget_name.clone()
-    }
-    pub fn get_current_budget_json_path(&self) -> String {
-        concat_paths(
-            &self.get_current_budget_path(),
-            &create_json_file_name(&self.state.current_focused_budget_name),
-        )
-    }
-
-    pub fn update_state(&self, new_state: &BudgeyState) -> Self {
-        Self::new(new_state, &self.config)
-    }
-    pub fn contains_budget(&self, budget_name: &str) -> bool {
-        self.state
-            .budget_names
-            .iter()
-            .any(|name| name == budget_name)
-    }
-}
+mod handle_reflog;
+mod handle_remote;
+mod handle_show;
+mod handle_status;
+mod handle_tag;
+mod handle_tui;
+mod output;
 
 fn main() -> anyhow::Result<()> {
     let home = env!("HOME").to_string();
@@ -91,32 +52,112 @@ fn main() -> anyhow::Result<()> {
 
     let state_json_name = create_json_file_name("budgey_state");
     let config = BudgeyConfig::new(&root_path, &state_json_name);
-    let args = budgey_cli::BudgeyCLI::parse();
 
     let state_io = StateIOImpl::new(&config);
     let budget_io = BudgetIOImpl::new(&config);
 
-    match args {
-        budgey_cli::BudgeyCLI::Init { name } => {
-            handle_init::handle_init(&name, &config, &state_io, &budget_io)
-        }
-        budgey_cli::BudgeyCLI::Subcommands(c) => {
-            let state = state_io.read_budgey_state();
-            if let Err(ref e) = state {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    println!(
-                        "Couldn't find the state file.\n\nPlease run {}",
-                        "`budgey init`".green()
-                    );
-                }
+    let state = state_io.read_budgey_state();
+    let focused_budget_name = state
+        .as_ref()
+        .ok()
+        .map(|state| state.current_focused_budget_name.clone());
+    // An invalid config file is reported once the output format is known. The defaults are used
+    // until then, so the file can still be fixed with `budgey config`.
+    let loaded = config::load_settings(&config, focused_budget_name.as_deref());
+    let settings = match &loaded {
+        Ok((settings, _)) => settings.clone(),
+        Err(_) => Settings::default(),
+    };
+
+    let args = BudgeyCLI::parse_from(expand_alias(std::env::args().collect(), &settings));
+    let output = Output::new(args.format, &settings);
+    match loaded {
+        Ok((_, ignored_keys)) => {
+            for key in ignored_keys {
+                output.warn(format!(
+                    "Ignoring {} in the config, it isn't a setting",
+                    key
+                ));
             }
+        }
+        Err(e) if args.command.is_config() => {
+            output.warn(format!("{}. Using the default settings", e));
+        }
+        Err(e) => return output.finish(Err(e)),
+    }
 
-            let context = BudgeyContext::new(&state?, &config);
-            let pile_io = PileIOImpl::new(&context);
+    let snapshot = if args.command.is_journaled() {
+        let budget_names = focused_budget_name
+            .iter()
+            .map(String::as_str)
+            .chain(args.command.named_budget())
+            .map(String::from)
+            .collect::<Vec<String>>();
+        Some(journal::snapshot(&config, &budget_names)?)
+    } else {
+        None
+    };
+
+    let result = match args.command {
+        BudgeyCommand::Init { name } => {
+            handle_init::handle_init(&name, &config, &state_io, &budget_io, &output)
+        }
+        BudgeyCommand::Clone { path, budget } => handle_remote::handle_clone(
+            &config,
+            state.as_ref().ok(),
+            &path,
+            budget.as_deref(),
+            &output,
+        ),
+        BudgeyCommand::Bundle { subcommand } => {
+            handle_bundle::handle_bundle(&config, state.as_ref().ok(), subcommand, &output)
+        }
+        BudgeyCommand::Reflog { limit } => handle_reflog::handle_reflog(&config, limit, &output),
+        BudgeyCommand::Undo { count } => handle_reflog::handle_undo(&config, count, &output),
+        BudgeyCommand::Subcommands(c) => match state {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                output.fail(format!(
+                    "Couldn't find the state file.\n\nPlease run {}",
+                    "`budgey init`".green()
+                ));
+                Ok(())
+            }
+            state => {
+                let context = BudgeyContext::new(&state?, &config).with_settings(&settings);
+                let pile_io = PileIOImpl::new(&context);
 
-            handle_subcommands(&context, c, &state_io, &budget_io, &pile_io)
+                handle_subcommands(&context, c, &state_io, &budget_io, &pile_io, &output)
+            }
+        },
+    };
+    // The command's changes are already written, so a journal failure only loses the undo entry.
+    if let (Some(snapshot), Ok(())) = (snapshot, &result) {
+        let command = std::env::args().skip(1).collect::<Vec<String>>().join(" ");
+        if let Err(e) = journal::record(&config, &command, &snapshot) {
+            output.warn(format!("couldn't write the undo journal: {}", e));
         }
     }
+    output.finish(result)
+}
+
+/// Replaces an alias given as the command with what it stands for, like Git aliases.
+/// Built in commands can't be overridden.
+fn expand_alias(args: Vec<String>, settings: &Settings) -> Vec<String> {
+    let Some(command) = args.get(1) else {
+        return args;
+    };
+    if BudgeyCLI::command().find_subcommand(command).is_some() {
+        return args;
+    }
+    match settings.alias.get(command) {
+        Some(alias) => args[..1]
+            .iter()
+            .cloned()
+            .chain(alias.split_whitespace().map(String::from))
+            .chain(args[2..].iter().cloned())
+            .collect(),
+        None => args,
+    }
 }
 
 fn handle_subcommands(
@@ -125,195 +166,295 @@ fn handle_subcommands(
     state_io: &impl StateIO,
     budget_io: &impl BudgetIO,
     pile_io: &impl PileIO,
+    output: &Output,
 ) -> anyhow::Result<()> {
     match command {
         Commands::Budget { subcommand } => {
             if let Some(sub) = subcommand {
-                handle_budget::handle_budget_subcommand(context, sub, state_io, budget_io, pile_io)
+                handle_budget::handle_budget_subcommand(
+                    context, sub, state_io, budget_io, pile_io, output,
+                )
             } else {
                 let current_budget = context.get_current_budget_name();
-                println!("Current budget: {:?}", current_budget);
-                Ok(())
+                output.println(format!("Current budget: {:?}", current_budget));
+                output.data(json!({ "budget": current_budget }))
             }
         }
         Commands::Pile { subcommand } => {
             if let Some(sub) = subcommand {
-                handle_pile::handle_pile_subcommand(context, sub, budget_io, pile_io)?;
+                handle_pile::handle_pile_subcommand(context, sub, budget_io, pile_io, output)?;
             } else {
                 let current_budget = budget_io.get_current_budget(context)?;
                 let current_pile = pile_io.get_current_pile(&current_budget)?;
-                println!("Current pile: {}", current_pile.get_name());
+                output.println(format!("Current pile: {}", current_pile.get_name()));
+                output.data(json!({ "pile": current_pile.get_name() }))?;
             }
             Ok(())
         }
-        Commands::Add { amount, note } => {
-            let amount = match evalexpr::eval(&amount) {
-                Ok(v) => v.as_number()?,
-                Err(e) => {
-                    println!("Invalid amount or expression. Please try again.");
-                    return Err(anyhow!("Invalid amount or expression: {:?}", e));
-                }
-            };
-            let new_pile = update_pile_with_action(
-                context,
-                |pile| {
-                    Ok(pile.add_transaction(&Transaction::new(
-                        TransactionType::Add,
-                        round_to_two_decimals(amount as f32),
-                        note.as_deref(),
-                    )))
-                },
-                budget_io,
-                pile_io,
-            )?;
-
-            println!(
-                "Staged transaction of {}. Pile now at: {}",
-                amount, new_pile.current_balance
-            );
-            Ok(())
-        }
-
-        Commands::Commit { message } => {
-            update_pile_with_action(
-                context,
-                |current_pile| {
-                    if current_pile.current_staged_transactions.is_empty() {
-                        println!("No staged transactions to commit. Add some transactions first.");
-                        return Ok(current_pile);
-                    }
-                    let current_time = utils::get_current_timestamp()?;
-                    let balance = current_pile.current_balance;
-
-                    let new_record = &Record::new(
-                        &message,
-                        &current_time,
-                        balance,
-                        &current_pile.current_staged_transactions,
-                    );
-
-                    let new_pile = current_pile
-                        .add_record(new_record)
-                        .clear_staged_transactions();
+        Commands::Add { transaction } => stage_transaction(
+            context,
+            TransactionType::Add,
+            &transaction,
+            budget_io,
+            pile_io,
+            output,
+        ),
 
-                    println!(
-                        "Record {} committed. Balance: {}",
-                        new_record.id, new_record.amount_after_record
-                    );
-                    Ok(new_pile)
-                },
-                budget_io,
-                pile_io,
-            )?;
-            Ok(())
-        }
-        Commands::Withdraw { amount, note } => {
-            let amount = match evalexpr::eval(&amount) {
-                Ok(v) => v.as_number()?,
-                Err(e) => {
-                    println!("Invalid amount or expression. Please try again.");
-                    return Err(anyhow!("Invalid amount or expression: {:?}", e));
-                }
+        Commands::Commit { message, force } => {
+            let current_budget = budget_io.get_current_budget(context)?;
+            let template_message = context
+                .settings()
+                .commit_message(&current_budget.current_pile_name);
+            let Some(message) = message.or(template_message) else {
+                output.fail("A commit message is required. Pass one with -m or set commit.template with `budgey config set`.");
+                return Ok(());
+            };
+            let current_pile = pile_io.get_current_pile(&current_budget)?;
+            let Some(new_record) = output.check(operations::commit(
+                context, &message, force, budget_io, pile_io,
+            ))?
+            else {
+                return Ok(());
             };
-            let new_pile = update_pile_with_action(
-                context,
-                |pile| {
-                    Ok(
-                        pile.add_transaction(&models::record_transaction::Transaction::new(
-                            TransactionType::Withdraw,
-                            round_to_two_decimals(amount as f32),
-                            note.as_deref(),
-                        )),
-                    )
-                },
-                budget_io,
-                pile_io,
-            )?;
 
-            println!(
-                "Staged transaction of {}. Pile now at: {}",
-                amount, new_pile.current_balance
+            let warnings = print_policy_warnings(
+                &current_pile,
+                &current_pile.current_staged_transactions,
+                output,
             );
-            Ok(())
+            output.println(format!(
+                "Record {} committed. Balance: {}",
+                new_record.short_id(),
+                output.amount(new_record.amount_after_record)
+            ));
+            output.data(json!({ "record": new_record, "warnings": warnings }))
         }
+        Commands::Withdraw { transaction } => stage_transaction(
+            context,
+            TransactionType::Withdraw,
+            &transaction,
+            budget_io,
+            pile_io,
+            output,
+        ),
         Commands::Restore => {
-            let updated_pile = update_pile_with_action(
-                context,
-                |pile| {
-                    let new_balance = pile
-                        .records
-                        .last()
-                        .map(|record| record.amount_after_record)
-                        .unwrap_or_else(|| pile.current_balance);
-                    let new_pile = pile.set_balance(new_balance);
+            let updated_pile = operations::restore(context, budget_io, pile_io)?;
 
-                    Ok(new_pile.clear_staged_transactions())
-                },
-                budget_io,
-                pile_io,
-            )?;
-
-            println!(
+            output.println(format!(
                 "Restored to last record. Pile now at: {}",
-                updated_pile.current_balance
-            );
-
-            Ok(())
+                output.amount(updated_pile.current_balance)
+            ));
+            output.data(json!({
+                "pile": updated_pile.get_name(),
+                "balance": updated_pile.current_balance,
+            }))
         }
-        Commands::Log => {
-            let current_pile = pile_io.get_current_pile(&budget_io.get_current_budget(context)?)?;
-
-            let records = current_pile.records;
-            println!(" --- Current Record ---");
-            for record in records.iter().rev() {
-                let record_indicator = "*".bold();
-                let separators = "|".bold();
-                let message = record.message.to_string().yellow();
-                let amount_after_record = if record.amount_after_record > 0.0 {
-                    format!("+{}", record.amount_after_record).green()
-                } else {
-                    format!("{}", record.amount_after_record).red()
-                };
-
-                println!("{} {} ", record_indicator, record.id.purple());
-                println!("{}", separators);
-                println!(
-                    "{}     Amount after record: {}",
-                    separators, amount_after_record
-                );
-                println!("{}     Message: {}", separators, message);
-
-                println!("{}", separators);
-            }
-            Ok(())
+        Commands::Log { options } => {
+            handle_log::handle_log(context, &options, budget_io, pile_io, output)
+        }
+        Commands::CherryPick { record_id, from } => handle_cherry_pick::handle_cherry_pick(
+            context,
+            &record_id,
+            from.as_deref(),
+            budget_io,
+            pile_io,
+            output,
+        ),
+        Commands::Diff { first, second } => handle_diff::handle_diff(
+            context,
+            &first,
+            second.as_deref(),
+            budget_io,
+            pile_io,
+            output,
+        ),
+        Commands::Show { record_id } => {
+            handle_show::handle_show(context, &record_id, budget_io, pile_io, output)
+        }
+        Commands::Tag {
+            name,
+            record_id,
+            subcommand,
+        } => handle_tag::handle_tag(
+            context, name, record_id, subcommand, budget_io, pile_io, output,
+        ),
+        Commands::Overview { at } => {
+            handle_overview::handle_overview(context, at.as_deref(), budget_io, pile_io, output)
+        }
+        Commands::Encrypt => handle_encryption::handle_encrypt(context, output),
+        Commands::Decrypt => handle_encryption::handle_decrypt(context, output),
+        Commands::Unlock { minutes } => handle_encryption::handle_unlock(context, minutes, output),
+        Commands::Lock => handle_encryption::handle_lock(context, output),
+        Commands::Config { subcommand } => {
+            handle_config::handle_config(context, subcommand, output)
+        }
+        Commands::Remote { subcommand } => {
+            handle_remote::handle_remote(context, subcommand, budget_io, output)
+        }
+        Commands::Push { remote } => {
+            handle_remote::handle_push(context, remote.as_deref(), budget_io, pile_io, output)
         }
+        Commands::Pull { remote } => {
+            handle_remote::handle_pull(context, remote.as_deref(), budget_io, pile_io, output)
+        }
+        Commands::Goal { subcommand } => {
+            handle_goal::handle_goal(context, subcommand, budget_io, pile_io, output)
+        }
+        Commands::Allocation { subcommand } => {
+            handle_allocation::handle_allocation(context, subcommand, budget_io, output)
+        }
+        Commands::Allocate { args } => {
+            handle_allocation::handle_allocate(context, &args, budget_io, pile_io, output)
+        }
+        Commands::Tui => handle_tui::handle_tui(context, budget_io, pile_io, output),
+        Commands::Status => handle_status::handle_status(context, budget_io, pile_io, output),
         Commands::Chain => {
             let current_pile = pile_io.get_current_pile(&budget_io.get_current_budget(context)?)?;
-            handle_showing_transactions(&current_pile)?;
-            Ok(())
+            handle_showing_transactions(&current_pile, output)?;
+            output.data(json!({
+                "pile": current_pile.get_name(),
+                "transactions": current_pile.current_staged_transactions,
+            }))
         }
     }
 }
 
-fn update_pile_with_action(
+fn stage_transaction(
     context: &BudgeyContext,
-    action: impl Fn(models::pile::Pile) -> anyhow::Result<models::pile::Pile>,
+    transaction_type: TransactionType,
+    args: &TransactionArgs,
     budget_io: &impl BudgetIO,
     pile_io: &impl PileIO,
-) -> anyhow::Result<models::pile::Pile> {
-    let current_budget = budget_io.get_current_budget(context)?;
-    let current_pile = pile_io.get_current_pile(&current_budget)?;
-    let new_pile = action(current_pile)?;
-    pile_io.update_pile(&new_pile)?;
-    Ok(new_pile)
+    output: &Output,
+) -> anyhow::Result<()> {
+    let variables = operations::amount_variables(context, budget_io, pile_io)?;
+    let Some(amount) = output.check(operations::evaluate_amount(&args.amount, &variables))? else {
+        return Ok(());
+    };
+    let date = match args.date.as_deref().map(utils::parse_date).transpose() {
+        Ok(date) => date,
+        Err(e) => {
+            output.fail(e);
+            return Ok(());
+        }
+    };
+    if date.is_some_and(|date| date > chrono::Local::now().date_naive()) {
+        output.fail("A transaction's date can't be in the future");
+        return Ok(());
+    }
+    let transaction =
+        Transaction::new(transaction_type, amount, args.note.as_deref()).with_date(date);
+    if !args.splits.is_empty() {
+        return stage_split_transaction(
+            context,
+            &transaction,
+            args,
+            &variables,
+            budget_io,
+            pile_io,
+            output,
+        );
+    }
+    let result =
+        operations::stage_transaction(context, &transaction, args.force, budget_io, pile_io);
+    let Some(new_pile) = output.check(result)? else {
+        return Ok(());
+    };
+    let warnings = print_policy_warnings(&new_pile, std::slice::from_ref(&transaction), output);
+
+    output.println(format!(
+        "Staged transaction of {}. Pile now at: {}",
+        output.amount(amount),
+        output.amount(new_pile.current_balance)
+    ));
+    output.data(json!({
+        "pile": new_pile.get_name(),
+        "transaction": transaction,
+        "balance": new_pile.current_balance,
+        "warnings": warnings,
+    }))
+}
+
+/// Prints a warning for each way the transactions break the pile's policy, and returns them.
+/// Only warnings, or refusals overridden with --force, are left by then.
+fn print_policy_warnings(
+    pile: &Pile,
+    transactions: &[Transaction],
+    output: &Output,
+) -> Vec<PolicyViolation> {
+    let violations = pile
+        .policy
+        .check(pile, transactions, chrono::Local::now().date_naive());
+    for violation in &violations {
+        output.println(format!("{} {}", "Warning:".yellow(), violation.message));
+    }
+    violations
+}
+
+fn stage_split_transaction(
+    context: &BudgeyContext,
+    transaction: &Transaction,
+    args: &TransactionArgs,
+    variables: &BTreeMap<String, f64>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+    output: &Output,
+) -> anyhow::Result<()> {
+    let result = operations::stage_split_transaction(
+        context,
+        transaction,
+        &args.splits,
+        variables,
+        args.force,
+        budget_io,
+        pile_io,
+    );
+    let Some(staged) = output.check(result)? else {
+        return Ok(());
+    };
+
+    output.println(format!(
+        "Split a transaction of {} across {} piles:",
+        output.amount(transaction.amount),
+        staged.len()
+    ));
+    let mut warnings = vec![];
+    for (pile, part) in &staged {
+        output.println(format!(
+            " {} {}: staged {}. Pile now at: {}",
+            "*".bold(),
+            pile.get_name(),
+            output.amount(part.amount),
+            output.amount(pile.current_balance)
+        ));
+        warnings.extend(print_policy_warnings(
+            pile,
+            std::slice::from_ref(part),
+            output,
+        ));
+    }
+    output.println("Focus each pile and commit to record its part.");
+    output.data(json!({
+        "split": transaction.amount,
+        "parts": staged
+            .iter()
+            .map(|(pile, part)| json!({
+                "pile": pile.get_name(),
+                "transaction": part,
+                "balance": pile.current_balance,
+            }))
+            .collect::<Vec<_>>(),
+        "warnings": warnings,
+    }))
 }
-fn handle_showing_transactions(current_pile: &models::pile::Pile) -> anyhow::Result<()> {
+
+fn handle_showing_transactions(current_pile: &Pile, output: &Output) -> anyhow::Result<()> {
     if current_pile.current_staged_transactions.is_empty() {
-        println!("No transactions in pile");
+        output.println("No transactions in pile");
         return Ok(());
     }
 
-    println!(" --- End of transaction chain ---");
+    output.println(" --- End of transaction chain ---");
     for (index, current_transaction) in current_pile
         .current_staged_transactions
         .iter()
@@ -322,29 +463,26 @@ fn handle_showing_transactions(current_pile: &models::pile::Pile) -> anyhow::Res
     {
         let transaction_indicator = "*".bold();
         let separators = "|\n|".bold();
-        let sign = match current_transaction.transaction_type {
-            TransactionType::Add => "+".green(),
-            TransactionType::Withdraw => "-".red(),
-            TransactionType::Init => "~".white(),
-        };
+        let sign = display::transaction_sign(current_transaction);
         let note = if let Some(note) = &current_transaction.note {
             note.to_string()
         } else {
             "".to_string()
         };
 
-        println!(
-            "{} {}{}    {}",
+        output.println(format!(
+            "{} {}{}    {}{}",
             transaction_indicator,
             sign,
             current_transaction.amount,
-            note.yellow()
-        );
+            note.yellow(),
+            display::transaction_date(current_transaction)
+        ));
         let start = index == 0;
         if !start {
-            println!("{}", separators);
+            output.println(&separators);
         }
     }
-    println!(" --- Start of transaction chain ---");
+    output.println(" --- Start of transaction chain ---");
     Ok(())
 }
@@ -1,3 +1,6 @@
+pub mod allocation;
 pub mod budget;
+pub mod goal;
 pub mod pile;
+pub mod policy;
 pub mod record_transaction;
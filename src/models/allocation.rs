@@ -0,0 +1,268 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{BudgeyError, BudgeyResult},
+    utils,
+};
+
+/// How an amount of income is split across piles, such as 50% to bills and the rest to savings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AllocationRule {
+    pub targets: Vec<AllocationTarget>,
+}
+
+/// A pile that gets part of an allocated amount.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AllocationTarget {
+    pub pile_name: String,
+    pub share: AllocationShare,
+    /// The least the pile gets, taken from what would otherwise be left over.
+    #[serde(default)]
+    pub minimum: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationShare {
+    /// A percentage of the amount.
+    Percent(f32),
+    /// A fixed amount.
+    Fixed(f32),
+    /// Whatever is left after the other targets.
+    Rest,
+}
+
+impl Display for AllocationShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllocationShare::Percent(percent) => write!(f, "{}%", percent),
+            AllocationShare::Fixed(amount) => write!(f, "{}", amount),
+            AllocationShare::Rest => write!(f, "rest"),
+        }
+    }
+}
+
+/// How an amount was split by a rule.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Allocation {
+    pub parts: Vec<AllocationPart>,
+    /// What no target took, when the rule has no `rest` target.
+    pub unallocated: f32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AllocationPart {
+    pub pile_name: String,
+    pub amount: f32,
+}
+
+impl AllocationTarget {
+    /// Parses a target given as <pile>=<share>, where the share is a percentage such as `50%`,
+    /// a fixed amount such as `200` or `rest`.
+    pub fn parse(text: &str) -> BudgeyResult<Self> {
+        let invalid = |message: String| BudgeyError::InvalidAllocationRule(message);
+        let (pile_name, share) = text
+            .split_once('=')
+            .filter(|(pile_name, _)| !pile_name.is_empty())
+            .ok_or_else(|| invalid(format!("{:?} should be <pile>=<share>", text)))?;
+        let share = share.trim();
+        let parse_number = |number: &str| {
+            number
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|number| number.is_finite() && *number > 0.0)
+                .ok_or_else(|| invalid(format!("the share of {} must be positive", pile_name)))
+        };
+        let share = if share == "rest" {
+            AllocationShare::Rest
+        } else if let Some(percent) = share.strip_suffix('%') {
+            AllocationShare::Percent(parse_number(percent)?)
+        } else {
+            AllocationShare::Fixed(parse_number(share)?)
+        };
+        Ok(Self {
+            pile_name: pile_name.to_string(),
+            share,
+            minimum: None,
+        })
+    }
+
+    pub fn with_minimum(self, minimum: Option<f32>) -> Self {
+        Self { minimum, ..self }
+    }
+}
+
+impl AllocationRule {
+    /// A rule with the given targets, checking they can be allocated together.
+    pub fn new(targets: Vec<AllocationTarget>) -> BudgeyResult<Self> {
+        let invalid = |message: String| Err(BudgeyError::InvalidAllocationRule(message));
+        if targets.is_empty() {
+            return invalid("a rule needs at least one target".to_string());
+        }
+        for (index, target) in targets.iter().enumerate() {
+            if targets[..index]
+                .iter()
+                .any(|other| other.pile_name == target.pile_name)
+            {
+                return invalid(format!("{} is named more than once", target.pile_name));
+            }
+            match (target.share, target.minimum) {
+                (AllocationShare::Fixed(_), Some(_)) => {
+                    return invalid(format!(
+                        "{} gets a fixed amount, so it can't have a minimum",
+                        target.pile_name
+                    ))
+                }
+                (_, Some(minimum)) if minimum <= 0.0 => {
+                    return invalid(format!(
+                        "the minimum of {} must be positive",
+                        target.pile_name
+                    ))
+                }
+                _ => {}
+            }
+        }
+        let percent: f32 = targets
+            .iter()
+            .filter_map(|target| match target.share {
+                AllocationShare::Percent(percent) => Some(percent),
+                _ => None,
+            })
+            .sum();
+        if percent > 100.0 {
+            return invalid(format!("the percentages add up to {}%", percent));
+        }
+        let rest_targets = targets
+            .iter()
+            .filter(|target| target.share == AllocationShare::Rest)
+            .count();
+        if rest_targets > 1 {
+            return invalid("only one target can take the rest".to_string());
+        }
+        Ok(Self { targets })
+    }
+
+    /// Splits an amount across the targets. Fixed amounts and percentages are taken first,
+    /// raised to their minimums, and the `rest` target gets what is left. Targets that would get
+    /// nothing are left out.
+    pub fn allocate(&self, amount: f32) -> BudgeyResult<Allocation> {
+        let cannot = |message: String| Err(BudgeyError::CannotAllocate(message));
+        let shares = self
+            .targets
+            .iter()
+            .map(|target| {
+                let share = match target.share {
+                    AllocationShare::Percent(percent) => {
+                        utils::round_to_two_decimals(amount * percent / 100.0)
+                    }
+                    AllocationShare::Fixed(fixed) => fixed,
+                    AllocationShare::Rest => return None,
+                };
+                Some(share.max(target.minimum.unwrap_or(0.0)))
+            })
+            .collect::<Vec<_>>();
+        let taken = utils::round_to_two_decimals(shares.iter().flatten().sum());
+        if taken > amount {
+            return cannot(format!(
+                "the rule needs at least {} but the amount is {}",
+                taken, amount
+            ));
+        }
+        let left = utils::round_to_two_decimals(amount - taken);
+
+        let mut parts = vec![];
+        let mut unallocated = left;
+        for (target, share) in self.targets.iter().zip(shares) {
+            let share = match share {
+                Some(share) => share,
+                None => {
+                    if target.minimum.is_some_and(|minimum| left < minimum) {
+                        return cannot(format!(
+                            "{} is left for {}, less than its minimum of {}",
+                            left,
+                            target.pile_name,
+                            target.minimum.unwrap_or_default()
+                        ));
+                    }
+                    unallocated = 0.0;
+                    left
+                }
+            };
+            if share > 0.0 {
+                parts.push(AllocationPart {
+                    pile_name: target.pile_name.to_string(),
+                    amount: share,
+                });
+            }
+        }
+        Ok(Allocation { parts, unallocated })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(targets: &[&str]) -> BudgeyResult<AllocationRule> {
+        AllocationRule::new(
+            targets
+                .iter()
+                .map(|target| AllocationTarget::parse(target))
+                .collect::<BudgeyResult<_>>()?,
+        )
+    }
+
+    fn parts(allocation: &Allocation) -> Vec<(&str, f32)> {
+        allocation
+            .parts
+            .iter()
+            .map(|part| (part.pile_name.as_str(), part.amount))
+            .collect()
+    }
+
+    #[test]
+    fn test_allocate() {
+        let payday = rule(&["bills=50%", "holiday=100", "savings=20%", "fun=rest"]).unwrap();
+        let allocation = payday.allocate(3000.0).unwrap();
+        assert_eq!(
+            vec![
+                ("bills", 1500.0),
+                ("holiday", 100.0),
+                ("savings", 600.0),
+                ("fun", 800.0)
+            ],
+            parts(&allocation)
+        );
+        assert_eq!(0.0, allocation.unallocated);
+        assert!(payday.allocate(50.0).is_err());
+
+        let mut targets = payday.targets.clone();
+        targets[2] = targets[2].clone().with_minimum(Some(500.0));
+        let with_minimum = AllocationRule::new(targets).unwrap();
+        assert_eq!(
+            vec![
+                ("bills", 1000.0),
+                ("holiday", 100.0),
+                ("savings", 500.0),
+                ("fun", 400.0)
+            ],
+            parts(&with_minimum.allocate(2000.0).unwrap())
+        );
+
+        let no_rest = rule(&["bills=50%", "savings=20%"]).unwrap();
+        assert_eq!(30.0, no_rest.allocate(100.0).unwrap().unallocated);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(rule(&[]).is_err());
+        assert!(rule(&["bills=60%", "savings=50%"]).is_err());
+        assert!(rule(&["bills=rest", "savings=rest"]).is_err());
+        assert!(rule(&["bills=50%", "bills=20%"]).is_err());
+        assert!(rule(&["bills=-5"]).is_err());
+        assert!(rule(&["bills"]).is_err());
+    }
+}
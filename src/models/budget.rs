@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
 use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
 
+use super::allocation::AllocationRule;
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct BudgetDetail {
     id: String,
@@ -18,11 +22,20 @@ impl BudgetDetail {
 
 /// Represents a collection of piles that a user might have
 /// Stored in /budgey/[budget_name]/
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Budget {
     pub budget_detail: BudgetDetail,
     pub pile_names: Vec<String>,
     pub current_pile_name: String,
+    /// Named references to records, keyed by tag name.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Paths of the budgey folders this budget is pushed to and pulled from, keyed by remote name.
+    #[serde(default)]
+    pub remotes: BTreeMap<String, String>,
+    /// Rules for splitting income across piles, keyed by rule name.
+    #[serde(default)]
+    pub allocation_rules: BTreeMap<String, AllocationRule>,
 }
 
 impl Budget {
@@ -30,11 +43,17 @@ impl Budget {
         budget_detail: BudgetDetail,
         pile_names: Vec<String>,
         current_pile_name: String,
+        tags: BTreeMap<String, String>,
+        remotes: BTreeMap<String, String>,
+        allocation_rules: BTreeMap<String, AllocationRule>,
     ) -> Self {
         Self {
             budget_detail,
             pile_names,
             current_pile_name,
+            tags,
+            remotes,
+            allocation_rules,
         }
     }
 
@@ -43,6 +62,20 @@ impl Budget {
             BudgetDetail::new(&nanoid!(), budget_name),
             vec!["main".to_string()],
             "main".to_string(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+        )
+    }
+    /// The same budget under another name, keeping its id.
+    pub fn rename(&self, budget_name: &str) -> Self {
+        Budget::new(
+            BudgetDetail::new(&self.budget_detail.id, budget_name),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            self.tags.clone(),
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
         )
     }
     pub fn change_current_pile(&self, name: &str) -> Self {
@@ -50,6 +83,9 @@ impl Budget {
             self.budget_detail.clone(),
             self.pile_names.clone(),
             name.to_string(),
+            self.tags.clone(),
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
         )
     }
     pub fn add_pile(&self, pile_name: &str) -> Self {
@@ -63,6 +99,9 @@ impl Budget {
             self.budget_detail.clone(),
             new_pile_names,
             self.current_pile_name.clone(),
+            self.tags.clone(),
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
         )
     }
     pub fn delete_pile(&self, pile_name: &str) -> Self {
@@ -81,6 +120,102 @@ impl Budget {
             self.budget_detail.clone(),
             new_pile_names,
             new_current_pile_name,
+            self.tags.clone(),
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
+        )
+    }
+    pub fn add_tag(&self, tag_name: &str, record_id: &str) -> Self {
+        let mut tags = self.tags.clone();
+        tags.insert(tag_name.to_string(), record_id.to_string());
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            tags,
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
+        )
+    }
+    pub fn delete_tag(&self, tag_name: &str) -> Self {
+        let mut tags = self.tags.clone();
+        tags.remove(tag_name);
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            tags,
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
+        )
+    }
+    /// Adds the other budget's tags, keeping this budget's tags where names clash.
+    pub fn merge_tags(&self, other_tags: &BTreeMap<String, String>) -> Self {
+        let mut tags = other_tags.clone();
+        tags.extend(self.tags.clone());
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            tags,
+            self.remotes.clone(),
+            self.allocation_rules.clone(),
         )
     }
+    pub fn add_remote(&self, remote_name: &str, path: &str) -> Self {
+        let mut remotes = self.remotes.clone();
+        remotes.insert(remote_name.to_string(), path.to_string());
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            self.tags.clone(),
+            remotes,
+            self.allocation_rules.clone(),
+        )
+    }
+    pub fn remove_remote(&self, remote_name: &str) -> Self {
+        let mut remotes = self.remotes.clone();
+        remotes.remove(remote_name);
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            self.tags.clone(),
+            remotes,
+            self.allocation_rules.clone(),
+        )
+    }
+    pub fn set_allocation_rule(&self, rule_name: &str, rule: &AllocationRule) -> Self {
+        let mut allocation_rules = self.allocation_rules.clone();
+        allocation_rules.insert(rule_name.to_string(), rule.clone());
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            self.tags.clone(),
+            self.remotes.clone(),
+            allocation_rules,
+        )
+    }
+    pub fn remove_allocation_rule(&self, rule_name: &str) -> Self {
+        let mut allocation_rules = self.allocation_rules.clone();
+        allocation_rules.remove(rule_name);
+        Budget::new(
+            self.budget_detail.clone(),
+            self.pile_names.clone(),
+            self.current_pile_name.clone(),
+            self.tags.clone(),
+            self.remotes.clone(),
+            allocation_rules,
+        )
+    }
+    /// Resolves a reference given by the user to a record id or id prefix.
+    /// Tag names resolve to the record they point at, anything else is returned unchanged.
+    pub fn resolve_reference<'a>(&'a self, reference: &'a str) -> &'a str {
+        self.tags
+            .get(reference)
+            .map(|record_id| record_id.as_str())
+            .unwrap_or(reference)
+    }
 }
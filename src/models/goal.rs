@@ -0,0 +1,151 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+use super::pile::Pile;
+
+/// How many days back a pile's inflow is averaged over to judge whether it is on pace.
+pub const RECENT_INFLOW_DAYS: u64 = 90;
+
+const DAYS_PER_MONTH: f32 = 30.44;
+
+/// An amount a pile is being saved up to by a date.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavingsGoal {
+    pub target: f32,
+    pub by: NaiveDate,
+    /// When the goal was set.
+    pub set_at: DateTime<Utc>,
+}
+
+/// How far a pile is towards its goal.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GoalProgress {
+    pub pile_name: String,
+    pub goal: SavingsGoal,
+    /// The pile's committed balance.
+    pub balance: f32,
+    /// How much is still to be saved, or 0 once the goal is reached.
+    pub remaining: f32,
+    /// The balance as a percentage of the target.
+    pub percent: f32,
+    /// The days until the goal's date, or 0 once it has passed.
+    pub days_left: i64,
+    /// What needs to be saved each month to reach the target by the date.
+    pub monthly_needed: f32,
+    /// The pile's average net inflow per month over the last `RECENT_INFLOW_DAYS` days.
+    pub monthly_inflow: f32,
+    pub status: GoalStatus,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalStatus {
+    /// The balance has reached the target.
+    Reached,
+    /// Recent inflow would reach the target by the date.
+    OnPace,
+    /// Recent inflow wouldn't reach the target by the date.
+    Behind,
+    /// The date has passed without reaching the target.
+    Overdue,
+}
+
+impl SavingsGoal {
+    pub fn new(target: f32, by: NaiveDate) -> Self {
+        Self {
+            target,
+            by,
+            set_at: Utc::now(),
+        }
+    }
+
+    /// How far the pile is towards this goal as of the given day.
+    /// Less than a month before the date, the whole remaining amount is needed that month.
+    pub fn progress(&self, pile: &Pile, today: NaiveDate) -> GoalProgress {
+        let balance = pile.last_committed_balance();
+        let remaining = utils::round_to_two_decimals((self.target - balance).max(0.0));
+        let days_left = (self.by - today).num_days().max(0);
+        let months_left = (days_left as f32 / DAYS_PER_MONTH).max(1.0);
+        let monthly_needed = utils::round_to_two_decimals(remaining / months_left);
+
+        let window_start = today
+            .checked_sub_days(chrono::Days::new(RECENT_INFLOW_DAYS))
+            .unwrap_or(NaiveDate::MIN);
+        let balance_then = pile
+            .balance_at(utils::start_of_day(window_start))
+            .unwrap_or(0.0);
+        let monthly_inflow = utils::round_to_two_decimals(
+            (balance - balance_then) * DAYS_PER_MONTH / RECENT_INFLOW_DAYS as f32,
+        );
+
+        let status = if remaining <= 0.0 {
+            GoalStatus::Reached
+        } else if today > self.by {
+            GoalStatus::Overdue
+        } else if monthly_inflow >= monthly_needed {
+            GoalStatus::OnPace
+        } else {
+            GoalStatus::Behind
+        };
+        GoalProgress {
+            pile_name: pile.get_name(),
+            goal: self.clone(),
+            balance,
+            remaining,
+            percent: utils::round_to_two_decimals(balance / self.target * 100.0),
+            days_left,
+            monthly_needed,
+            monthly_inflow,
+            status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::record_transaction::{Transaction, TransactionType},
+        test_utils::commit,
+    };
+
+    fn pile_with_records(records: &[(NaiveDate, f32)]) -> Pile {
+        records
+            .iter()
+            .fold(Pile::default_main_pile(), |pile, (date, amount)| {
+                let transaction = Transaction::new(TransactionType::Add, *amount, None);
+                commit(pile, "saving", transaction, utils::start_of_day(*date))
+            })
+    }
+
+    #[test]
+    fn test_progress() {
+        let date = |text| NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap();
+        let today = date("2026-10-01");
+        let pile = pile_with_records(&[
+            (date("2026-01-10"), 400.0),
+            (date("2026-08-01"), 300.0),
+            (date("2026-09-01"), 300.0),
+        ]);
+        let goal = SavingsGoal::new(2000.0, date("2027-04-01"));
+
+        let progress = goal.progress(&pile, today);
+        assert_eq!(1000.0, progress.remaining);
+        assert_eq!(50.0, progress.percent);
+        assert_eq!(182, progress.days_left);
+        assert_eq!(167.25, progress.monthly_needed);
+        assert_eq!(202.93, progress.monthly_inflow);
+        assert_eq!(GoalStatus::OnPace, progress.status);
+
+        let sooner = SavingsGoal::new(2000.0, date("2026-12-01"));
+        assert_eq!(GoalStatus::Behind, sooner.progress(&pile, today).status);
+        assert_eq!(
+            GoalStatus::Overdue,
+            sooner.progress(&pile, date("2026-12-02")).status
+        );
+        let reached = SavingsGoal::new(1000.0, date("2026-12-01"));
+        assert_eq!(GoalStatus::Reached, reached.progress(&pile, today).status);
+    }
+}
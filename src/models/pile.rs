@@ -1,6 +1,13 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::record_transaction::{Record, Transaction};
+use crate::utils;
+
+use super::{
+    goal::SavingsGoal,
+    policy::PilePolicy,
+    record_transaction::{Record, Transaction},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Pile {
@@ -8,6 +15,16 @@ pub struct Pile {
     pub pile_type: PileType,
     pub records: Vec<Record>,
     pub current_staged_transactions: Vec<Transaction>,
+    /// The amount this pile is being saved up to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal: Option<SavingsGoal>,
+    /// The limits this pile's withdrawals are held to.
+    #[serde(default, skip_serializing_if = "PilePolicy::is_default")]
+    pub policy: PilePolicy,
+    /// The id of the last record this pile took from another pile when it was created or
+    /// rebased. The records up to it were made on the other pile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_record_id: Option<String>,
 }
 
 impl Default for Pile {
@@ -15,12 +32,38 @@ impl Default for Pile {
         Pile::new(
             0.0,
             &PileType::Main,
-            &[Record::new_init("Initialised main", "0", 0.0, Some(0.0))],
+            &[Record::new_init(
+                "Initialised main",
+                DateTime::UNIX_EPOCH,
+                0.0,
+                Some(0.0),
+            )],
             &[],
         )
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum RecordLookupError {
+    #[error("Couldn't find a record matching {0}")]
+    NotFound(String),
+    #[error("Record id {0} is ambiguous. Use more characters.")]
+    Ambiguous(String),
+}
+
+/// How a pile's record history compares to another pile's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryComparison {
+    /// Both piles have the same records.
+    Same,
+    /// This pile has every record of the other, plus this many more.
+    Ahead(usize),
+    /// The other pile has every record of this one, plus this many more.
+    Behind(usize),
+    /// Each pile has records the other doesn't.
+    Diverged,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum PileType {
     #[serde(rename = "main")]
@@ -36,63 +79,209 @@ impl Pile {
             PileType::UserCreated { pile_name } => pile_name.to_string(),
         }
     }
+    /// Finds a record in this pile's history by its id.
+    pub fn find_record(&self, record_id: &str) -> Option<&Record> {
+        self.records.iter().find(|record| record.id == record_id)
+    }
+    /// The records made on this pile, after those it took from another pile when it was created
+    /// or rebased.
+    pub fn own_records(&self) -> &[Record] {
+        let start = self
+            .base_record_id
+            .as_ref()
+            .and_then(|id| self.records.iter().position(|record| &record.id == id))
+            .map_or(0, |base| base + 1);
+        &self.records[start..]
+    }
+    /// The balance of this pile as of its last record, ignoring staged transactions.
+    pub fn last_committed_balance(&self) -> f32 {
+        self.records
+            .last()
+            .map(|record| record.amount_after_record)
+            .unwrap_or(self.current_balance)
+    }
+    /// The amount the staged transactions change this pile's balance by.
+    pub fn staged_balance_change(&self) -> f32 {
+        let change: f32 = self
+            .current_staged_transactions
+            .iter()
+            .map(|transaction| transaction.balance_change())
+            .sum();
+        utils::round_to_two_decimals(change)
+    }
+    /// The balance this pile had at the given time, based on its committed records.
+    /// Returns None if the pile had no records by then.
+    ///
+    /// A transaction counts from the start of its date if it has one, such as a backdated
+    /// receipt, and otherwise from when its record was made. Records aren't always in time order,
+    /// for example after a rebase, so the changes of every transaction made by then are added to
+    /// the balance the history starts from.
+    pub fn balance_at(&self, time_stamp: DateTime<Utc>) -> Option<f32> {
+        let made = self
+            .records
+            .iter()
+            .flat_map(|record| {
+                record.transactions.iter().filter(move |transaction| {
+                    let made_at = match transaction.date {
+                        Some(date) => utils::start_of_day(date),
+                        None => record.time_stamp,
+                    };
+                    made_at <= time_stamp
+                })
+            })
+            .collect::<Vec<&Transaction>>();
+        if made.is_empty() {
+            return None;
+        }
+        let start = self.records.first()?.amount_before_record();
+        let change: f32 = made
+            .iter()
+            .map(|transaction| transaction.balance_change())
+            .sum();
+        Some(utils::round_to_two_decimals(start + change))
+    }
+    /// Resolves a record from its full id or any unique prefix of it.
+    pub fn resolve_record(&self, id_or_prefix: &str) -> Result<&Record, RecordLookupError> {
+        if let Some(record) = self.find_record(id_or_prefix) {
+            return Ok(record);
+        }
+        let mut matches = self
+            .records
+            .iter()
+            .filter(|record| record.id.starts_with(id_or_prefix));
+        match (matches.next(), matches.next()) {
+            (Some(record), None) if !id_or_prefix.is_empty() => Ok(record),
+            (Some(_), _) => Err(RecordLookupError::Ambiguous(id_or_prefix.to_string())),
+            (None, _) => Err(RecordLookupError::NotFound(id_or_prefix.to_string())),
+        }
+    }
+    /// The number of records at the start of this pile's history that are shared with the other
+    /// pile.
+    pub fn shared_history_len(&self, other: &Pile) -> usize {
+        self.records
+            .iter()
+            .zip(other.records.iter())
+            .take_while(|(own, theirs)| own.id == theirs.id)
+            .count()
+    }
+    /// Compares this pile's record history with another pile's.
+    pub fn compare_history(&self, other: &Pile) -> HistoryComparison {
+        let shared = self.shared_history_len(other);
+        match (shared == self.records.len(), shared == other.records.len()) {
+            (true, true) => HistoryComparison::Same,
+            (false, true) => HistoryComparison::Ahead(self.records.len() - shared),
+            (true, false) => HistoryComparison::Behind(other.records.len() - shared),
+            (false, false) => HistoryComparison::Diverged,
+        }
+    }
+    /// Takes the record history of a pile that is ahead of this one, keeping this pile's staged
+    /// transactions on top of it.
+    pub fn fast_forward_to(self, ahead: &Pile) -> Self {
+        let last_balance = ahead.last_committed_balance();
+        let balance = utils::round_to_two_decimals(last_balance + self.staged_balance_change());
+        Self {
+            current_balance: balance,
+            records: ahead.records.clone(),
+            ..self
+        }
+    }
+    /// A copy of this pile without its staged transactions.
+    pub fn committed_copy(&self) -> Self {
+        Self {
+            current_balance: self.last_committed_balance(),
+            current_staged_transactions: vec![],
+            ..self.clone()
+        }
+    }
+    /// Replays the records this pile has made since it diverged from `onto` on top of `onto`'s
+    /// history, recomputing the balance after each replayed record.
+    pub fn rebase_onto(self, onto: &Pile) -> Self {
+        let shared = self.shared_history_len(onto);
+        let mut balance = onto
+            .records
+            .last()
+            .map(|record| record.amount_after_record)
+            .unwrap_or(onto.current_balance);
+        let replayed = self.records[shared..].iter().map(|record| {
+            balance = utils::round_to_two_decimals(balance + record.balance_change());
+            record.clone().set_amount_after_record(balance)
+        });
+        let records = onto
+            .records
+            .iter()
+            .cloned()
+            .chain(replayed)
+            .collect::<Vec<Record>>();
+        let last_balance = records
+            .last()
+            .map(|record| record.amount_after_record)
+            .unwrap_or(balance);
+        Self {
+            current_balance: utils::round_to_two_decimals(
+                last_balance + self.staged_balance_change(),
+            ),
+            records,
+            base_record_id: onto.records.last().map(|record| record.id.clone()),
+            ..self
+        }
+    }
     pub fn add_transaction(self, transaction: &Transaction) -> Self {
-        Self::new(
-            match transaction.transaction_type {
-                super::record_transaction::TransactionType::Add => {
-                    self.current_balance + transaction.amount
-                }
-                super::record_transaction::TransactionType::Withdraw => {
-                    self.current_balance - transaction.amount
-                }
-                super::record_transaction::TransactionType::Init => self.current_balance,
-            },
-            &self.pile_type,
-            &self.records,
-            self.clone()
-                .current_staged_transactions
-                .into_iter()
-                .chain(vec![transaction.clone()])
-                .collect::<Vec<Transaction>>()
-                .as_slice(),
-        )
+        let current_balance = match transaction.transaction_type {
+            super::record_transaction::TransactionType::Add => {
+                self.current_balance + transaction.amount
+            }
+            super::record_transaction::TransactionType::Withdraw => {
+                self.current_balance - transaction.amount
+            }
+            super::record_transaction::TransactionType::Init => self.current_balance,
+        };
+        let mut current_staged_transactions = self.current_staged_transactions;
+        current_staged_transactions.push(transaction.clone());
+        Self {
+            current_balance,
+            current_staged_transactions,
+            ..self
+        }
     }
     pub fn add_record(self, record: &Record) -> Self {
-        Self::new(
-            self.current_balance,
-            &self.pile_type,
-            self.records
-                .into_iter()
-                .chain(vec![record.clone()])
-                .collect::<Vec<Record>>()
-                .as_slice(),
-            &self.current_staged_transactions,
-        )
+        let mut records = self.records;
+        records.push(record.clone());
+        Self { records, ..self }
     }
     pub fn clear_staged_transactions(self) -> Self {
-        Self::new(self.current_balance, &self.pile_type, &self.records, &[])
+        Self {
+            current_staged_transactions: vec![],
+            ..self
+        }
     }
     pub fn set_balance(self, new_balance: f32) -> Self {
-        Self::new(
-            new_balance,
-            &self.pile_type,
-            &self.records,
-            &self.current_staged_transactions,
-        )
+        Self {
+            current_balance: new_balance,
+            ..self
+        }
+    }
+    pub fn with_goal(self, goal: Option<SavingsGoal>) -> Self {
+        Self { goal, ..self }
+    }
+    pub fn with_policy(self, policy: PilePolicy) -> Self {
+        Self { policy, ..self }
     }
     pub fn new_user_created(
         balance: f32,
         pile_name: &str,
         source_record_history: &[Record],
     ) -> Self {
-        Self::new(
-            balance,
-            &PileType::UserCreated {
-                pile_name: pile_name.to_string(),
-            },
-            source_record_history,
-            &[],
-        )
+        Self {
+            base_record_id: source_record_history.last().map(|record| record.id.clone()),
+            ..Self::new(
+                balance,
+                &PileType::UserCreated {
+                    pile_name: pile_name.to_string(),
+                },
+                source_record_history,
+                &[],
+            )
+        }
     }
     pub fn new(
         balance: f32,
@@ -106,14 +295,194 @@ impl Pile {
             pile_type: pile_type.clone(),
             records: up_to_date_history,
             current_staged_transactions: transactions.to_vec(),
+            goal: None,
+            policy: PilePolicy::default(),
+            base_record_id: None,
         }
     }
     pub fn default_main_pile() -> Pile {
         Pile::new(
             0.0,
             &PileType::Main,
-            &[Record::new_init("Initialised main", "0", 0.0, Some(0.0))],
+            &[Record::new_init(
+                "Initialised main",
+                DateTime::UNIX_EPOCH,
+                0.0,
+                Some(0.0),
+            )],
             &[],
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{models::record_transaction::TransactionType, test_utils::commit};
+
+    #[test]
+    fn test_builders_keep_goal_and_policy() {
+        let goal = SavingsGoal::new(500.0, NaiveDate::from_ymd_opt(2027, 6, 1).unwrap());
+        let policy = PilePolicy {
+            max_withdrawal: Some(50.0),
+            ..PilePolicy::default()
+        };
+        let main = Pile::default_main_pile();
+        let pile = Pile::new_user_created(0.0, "holiday", &main.records)
+            .with_goal(Some(goal.clone()))
+            .with_policy(policy.clone());
+        let pile = commit(
+            pile,
+            "saving",
+            Transaction::new(TransactionType::Add, 20.0, None),
+            DateTime::UNIX_EPOCH,
+        )
+        .set_balance(20.0)
+        .add_transaction(&Transaction::new(TransactionType::Withdraw, 5.0, None));
+        for built in [
+            pile.committed_copy(),
+            pile.clone().fast_forward_to(&pile),
+            pile.rebase_onto(&main),
+        ] {
+            assert_eq!(Some(&goal), built.goal.as_ref());
+            assert_eq!(policy, built.policy);
+        }
+    }
+
+    #[test]
+    fn test_compare_history_and_fast_forward() {
+        let base = Pile::default_main_pile();
+        let ahead = commit(
+            base.clone(),
+            "salary",
+            Transaction::new(TransactionType::Add, 100.0, None),
+            DateTime::UNIX_EPOCH,
+        );
+        let diverged = commit(
+            base.clone(),
+            "rent",
+            Transaction::new(TransactionType::Withdraw, 50.0, None),
+            DateTime::UNIX_EPOCH,
+        );
+        assert_eq!(HistoryComparison::Same, base.compare_history(&base));
+        assert_eq!(HistoryComparison::Ahead(1), ahead.compare_history(&base));
+        assert_eq!(HistoryComparison::Behind(1), base.compare_history(&ahead));
+        assert_eq!(
+            HistoryComparison::Diverged,
+            ahead.compare_history(&diverged)
+        );
+
+        let staged = base.add_transaction(&Transaction::new(TransactionType::Withdraw, 5.0, None));
+        let fast_forwarded = staged.fast_forward_to(&ahead);
+        assert_eq!(2, fast_forwarded.records.len());
+        assert_eq!(1, fast_forwarded.current_staged_transactions.len());
+        assert_eq!(95.0, fast_forwarded.current_balance);
+    }
+
+    #[test]
+    fn test_rebase_onto_replays_own_records() {
+        let main = Pile::default_main_pile();
+        let side = Pile::new_user_created(main.current_balance, "side", &main.records);
+        let side = commit(
+            side,
+            "side",
+            Transaction::new(TransactionType::Withdraw, 5.0, None),
+            DateTime::UNIX_EPOCH,
+        );
+        let main = commit(
+            main,
+            "salary",
+            Transaction::new(TransactionType::Add, 100.0, None),
+            DateTime::UNIX_EPOCH,
+        );
+
+        let rebased = side.rebase_onto(&main);
+
+        assert_eq!(3, rebased.records.len());
+        assert_eq!(main.records[1].id, rebased.records[1].id);
+        assert_eq!("side", rebased.records[2].message);
+        assert_eq!(95.0, rebased.records[2].amount_after_record);
+        assert_eq!(95.0, rebased.current_balance);
+        assert_eq!(2, rebased.shared_history_len(&main));
+    }
+
+    #[test]
+    fn test_balance_at_with_records_out_of_time_order() {
+        let day = |day: i64| DateTime::UNIX_EPOCH + chrono::TimeDelta::days(day);
+        let record = |time_stamp, amount_after_record, transaction_type, amount| {
+            let transaction = Transaction::new(transaction_type, amount, None);
+            Record::new("", time_stamp, amount_after_record, &[transaction])
+        };
+        // As left by a rebase that replays a withdrawal from day 2 after a deposit from day 5.
+        let pile = Pile::new(
+            70.0,
+            &PileType::Main,
+            &[
+                record(day(0), 0.0, TransactionType::Init, 0.0),
+                record(day(5), 100.0, TransactionType::Add, 100.0),
+                record(day(2), 70.0, TransactionType::Withdraw, 30.0),
+            ],
+            &[],
+        );
+
+        assert_eq!(None, pile.balance_at(day(-1)));
+        assert_eq!(Some(0.0), pile.balance_at(day(1)));
+        assert_eq!(Some(-30.0), pile.balance_at(day(3)));
+        assert_eq!(Some(70.0), pile.balance_at(day(6)));
+    }
+
+    #[test]
+    fn test_balance_at_counts_backdated_transactions_from_their_date() {
+        let date = |text| NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap();
+        let receipt = Transaction::new(TransactionType::Withdraw, 30.0, None)
+            .with_date(Some(date("2026-03-02")));
+        let pile = commit(
+            Pile::default_main_pile(),
+            "salary",
+            Transaction::new(TransactionType::Add, 100.0, None),
+            DateTime::UNIX_EPOCH,
+        );
+        let pile = commit(
+            pile,
+            "receipt",
+            receipt,
+            utils::start_of_day(date("2026-03-10")),
+        );
+
+        assert_eq!(
+            Some(100.0),
+            pile.balance_at(utils::end_of_day(date("2026-03-01")))
+        );
+        assert_eq!(
+            Some(70.0),
+            pile.balance_at(utils::end_of_day(date("2026-03-02")))
+        );
+    }
+
+    #[test]
+    fn test_resolve_record_by_prefix() {
+        let record = |id: &str| Record {
+            id: id.to_string(),
+            ..Record::new("", DateTime::UNIX_EPOCH, 0.0, &[])
+        };
+        let pile = Pile::new(
+            0.0,
+            &PileType::Main,
+            &[record("abc123"), record("abd456"), record("xyz")],
+            &[],
+        );
+
+        assert_eq!("abc123", pile.resolve_record("abc").unwrap().id);
+        assert_eq!("xyz", pile.resolve_record("xyz").unwrap().id);
+        assert!(matches!(
+            pile.resolve_record("ab"),
+            Err(RecordLookupError::Ambiguous(_))
+        ));
+        assert!(matches!(
+            pile.resolve_record("q"),
+            Err(RecordLookupError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+use super::{
+    pile::Pile,
+    record_transaction::{Transaction, TransactionType},
+};
+
+/// The limits a pile's withdrawals are held to.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PilePolicy {
+    /// What happens when withdrawals take the balance below zero.
+    #[serde(default)]
+    pub overdraft: OverdraftPolicy,
+    /// The most a single withdrawal can take.
+    #[serde(default)]
+    pub max_withdrawal: Option<f32>,
+    /// The most that can be withdrawn in each day, week or month.
+    #[serde(default)]
+    pub spending_cap: Option<SpendingCap>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverdraftPolicy {
+    /// Negative balances are allowed.
+    #[default]
+    Allow,
+    /// Negative balances are allowed with a warning.
+    Warn,
+    /// Negative balances are refused.
+    Forbid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SpendingCap {
+    pub amount: f32,
+    pub period: SpendingPeriod,
+}
+
+/// A calendar period in local time. Weeks start on Monday.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpendingPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+/// A way staged transactions break a pile's policy.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PolicyViolation {
+    pub message: String,
+    /// Whether the policy refuses the transactions, rather than just warning about them.
+    pub refused: bool,
+}
+
+impl FromStr for OverdraftPolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "allow" => Ok(OverdraftPolicy::Allow),
+            "warn" => Ok(OverdraftPolicy::Warn),
+            "forbid" => Ok(OverdraftPolicy::Forbid),
+            _ => Err(format!("{:?} isn't allow, warn or forbid", text)),
+        }
+    }
+}
+
+impl Display for OverdraftPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverdraftPolicy::Allow => write!(f, "allow"),
+            OverdraftPolicy::Warn => write!(f, "warn"),
+            OverdraftPolicy::Forbid => write!(f, "forbid"),
+        }
+    }
+}
+
+impl FromStr for SpendingPeriod {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "day" => Ok(SpendingPeriod::Day),
+            "week" => Ok(SpendingPeriod::Week),
+            "month" => Ok(SpendingPeriod::Month),
+            _ => Err(format!("{:?} isn't day, week or month", text)),
+        }
+    }
+}
+
+impl Display for SpendingPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpendingPeriod::Day => write!(f, "day"),
+            SpendingPeriod::Week => write!(f, "week"),
+            SpendingPeriod::Month => write!(f, "month"),
+        }
+    }
+}
+
+impl SpendingPeriod {
+    /// Whether the date falls in the same period as today.
+    fn contains(&self, date: NaiveDate, today: NaiveDate) -> bool {
+        match self {
+            SpendingPeriod::Day => date == today,
+            SpendingPeriod::Week => date.iso_week() == today.iso_week(),
+            SpendingPeriod::Month => date.year() == today.year() && date.month() == today.month(),
+        }
+    }
+}
+
+impl PilePolicy {
+    pub fn is_default(&self) -> bool {
+        *self == PilePolicy::default()
+    }
+
+    /// Checks the given transactions, already staged on the pile, against this policy.
+    ///
+    /// The balance only breaks the overdraft policy if one of the transactions takes it below
+    /// zero and lower than it was just before, so money can still be added to a pile that is
+    /// overdrawn.
+    pub fn check(
+        &self,
+        pile: &Pile,
+        transactions: &[Transaction],
+        today: NaiveDate,
+    ) -> Vec<PolicyViolation> {
+        let pile_name = pile.get_name();
+        let withdrawals = |transactions: &mut dyn Iterator<Item = &Transaction>| {
+            transactions
+                .filter(|transaction| transaction.transaction_type == TransactionType::Withdraw)
+                .map(|transaction| transaction.amount)
+                .collect::<Vec<f32>>()
+        };
+        let checked = withdrawals(&mut transactions.iter());
+        let mut violations = vec![];
+
+        let change: f32 = transactions.iter().map(Transaction::balance_change).sum();
+        let mut balance = pile.current_balance - change;
+        let mut overdrawn_to = None;
+        for transaction in transactions {
+            let before = balance;
+            balance += transaction.balance_change();
+            if balance < 0.0 && balance < before {
+                overdrawn_to =
+                    Some(overdrawn_to.map_or(balance, |lowest: f32| lowest.min(balance)));
+            }
+        }
+        if let Some(overdrawn_to) = overdrawn_to {
+            let message = format!(
+                "Pile {} would go down to {}",
+                pile_name,
+                utils::round_to_two_decimals(overdrawn_to)
+            );
+            match self.overdraft {
+                OverdraftPolicy::Allow => {}
+                OverdraftPolicy::Warn => violations.push(PolicyViolation {
+                    message,
+                    refused: false,
+                }),
+                OverdraftPolicy::Forbid => violations.push(PolicyViolation {
+                    message: format!("{}, and its policy forbids negative balances", message),
+                    refused: true,
+                }),
+            }
+        }
+
+        if let Some(max_withdrawal) = self.max_withdrawal {
+            for amount in checked.iter().filter(|amount| **amount > max_withdrawal) {
+                violations.push(PolicyViolation {
+                    message: format!(
+                        "Withdrawing {} is over pile {}'s limit of {} per withdrawal",
+                        amount, pile_name, max_withdrawal
+                    ),
+                    refused: true,
+                });
+            }
+        }
+
+        if let Some(cap) = self.spending_cap.filter(|_| !checked.is_empty()) {
+            let in_period = |date: NaiveDate| cap.period.contains(date, today);
+            // Records the pile took from another pile were spent from that pile.
+            let committed = pile.own_records().iter().flat_map(|record| {
+                record
+                    .transactions
+                    .iter()
+                    .filter(move |transaction| in_period(record.transaction_date(transaction)))
+            });
+            let staged = pile
+                .current_staged_transactions
+                .iter()
+                .filter(|transaction| in_period(transaction.date.unwrap_or(today)));
+            let spent: f32 = withdrawals(&mut committed.chain(staged)).iter().sum();
+            if spent > cap.amount {
+                violations.push(PolicyViolation {
+                    message: format!(
+                        "Withdrawals from pile {} this {} would come to {}, over its cap of {}",
+                        pile_name,
+                        cap.period,
+                        utils::round_to_two_decimals(spent),
+                        cap.amount
+                    ),
+                    refused: true,
+                });
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, Utc};
+
+    use super::*;
+    use crate::test_utils::commit;
+
+    fn withdraw(amount: f32) -> Transaction {
+        Transaction::new(TransactionType::Withdraw, amount, None)
+    }
+
+    fn deposit(amount: f32) -> Transaction {
+        Transaction::new(TransactionType::Add, amount, None)
+    }
+
+    /// The main pile with 100 committed to it today.
+    fn pile_with_salary() -> Pile {
+        commit(
+            Pile::default_main_pile(),
+            "salary",
+            deposit(100.0),
+            Utc::now(),
+        )
+    }
+
+    fn overdraft(overdraft: OverdraftPolicy) -> PilePolicy {
+        PilePolicy {
+            overdraft,
+            ..PilePolicy::default()
+        }
+    }
+
+    fn monthly_cap(amount: f32) -> PilePolicy {
+        PilePolicy {
+            spending_cap: Some(SpendingCap {
+                amount,
+                period: SpendingPeriod::Month,
+            }),
+            ..PilePolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_default_policy_allows_anything() {
+        let today = Local::now().date_naive();
+        let staged = pile_with_salary().add_transaction(&withdraw(160.0));
+        assert!(PilePolicy::default()
+            .check(&staged, &[withdraw(160.0)], today)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_max_withdrawal() {
+        let today = Local::now().date_naive();
+        let policy = PilePolicy {
+            max_withdrawal: Some(50.0),
+            ..PilePolicy::default()
+        };
+        let staged = pile_with_salary().add_transaction(&withdraw(50.0));
+        assert!(policy.check(&staged, &[withdraw(50.0)], today).is_empty());
+        let staged = staged.add_transaction(&withdraw(60.0));
+        let violations = policy.check(&staged, &[withdraw(60.0)], today);
+        assert_eq!(1, violations.len());
+        assert!(violations[0].refused);
+    }
+
+    #[test]
+    fn test_overdraft_warns_or_forbids() {
+        let today = Local::now().date_naive();
+        let overdrawn = pile_with_salary().add_transaction(&withdraw(120.0));
+        assert!(overdraft(OverdraftPolicy::Allow)
+            .check(&overdrawn, &[withdraw(120.0)], today)
+            .is_empty());
+        let violations =
+            overdraft(OverdraftPolicy::Warn).check(&overdrawn, &[withdraw(120.0)], today);
+        assert_eq!(1, violations.len());
+        assert!(!violations[0].refused);
+        let forbid = overdraft(OverdraftPolicy::Forbid);
+        assert!(forbid.check(&overdrawn, &[withdraw(120.0)], today)[0].refused);
+
+        // Money can still be added to a pile that is overdrawn.
+        let topped_up = overdrawn.add_transaction(&deposit(10.0));
+        assert!(forbid.check(&topped_up, &[deposit(10.0)], today).is_empty());
+    }
+
+    #[test]
+    fn test_overdraft_checks_the_balance_each_transaction_leaves() {
+        let today = Local::now().date_naive();
+        let forbid = overdraft(OverdraftPolicy::Forbid);
+        // Adding 100 to a pile at -50 and then withdrawing 70 takes it from 50 to -20, even though
+        // the staged transactions add up to more than they take.
+        let overdrawn = commit(
+            Pile::default_main_pile(),
+            "rent",
+            withdraw(50.0),
+            Utc::now(),
+        );
+        let staged = overdrawn.add_transaction(&deposit(100.0));
+        assert!(forbid.check(&staged, &[deposit(100.0)], today).is_empty());
+        let staged = staged.add_transaction(&withdraw(70.0));
+        assert_eq!(30.0, staged.staged_balance_change());
+        let violations = forbid.check(&staged, &[withdraw(70.0)], today);
+        assert_eq!(
+            "Pile main would go down to -20, and its policy forbids negative balances",
+            violations[0].message
+        );
+        assert!(forbid.check(&staged, &[deposit(100.0), withdraw(70.0)], today)[0].refused);
+    }
+
+    #[test]
+    fn test_spending_cap_counts_committed_and_staged_withdrawals() {
+        let today = Local::now().date_naive();
+        let capped = monthly_cap(100.0);
+        let pile = commit(pile_with_salary(), "groceries", withdraw(40.0), Utc::now());
+        let staged = pile.add_transaction(&withdraw(60.0));
+        assert!(capped.check(&staged, &[withdraw(60.0)], today).is_empty());
+        let over_cap = staged.add_transaction(&withdraw(10.0));
+        assert!(capped.check(&over_cap, &[withdraw(10.0)], today)[0].refused);
+    }
+
+    #[test]
+    fn test_spending_cap_ignores_records_taken_from_another_pile() {
+        let today = Local::now().date_naive();
+        let capped = monthly_cap(100.0);
+        let main = commit(pile_with_salary(), "groceries", withdraw(90.0), Utc::now());
+        let fork = Pile::new_user_created(main.current_balance, "fun", &main.records);
+        let staged = fork.clone().add_transaction(&withdraw(60.0));
+        assert!(capped.check(&staged, &[withdraw(60.0)], today).is_empty());
+
+        // Rebasing onto main takes main's records too, which still don't count.
+        let fork = commit(fork, "cinema", withdraw(30.0), Utc::now());
+        let main = commit(main, "petrol", withdraw(5.0), Utc::now());
+        let rebased = fork.rebase_onto(&main);
+        assert_eq!(1, rebased.own_records().len());
+        let staged = rebased.add_transaction(&withdraw(60.0));
+        assert!(capped.check(&staged, &[withdraw(60.0)], today).is_empty());
+        let over_cap = staged.add_transaction(&withdraw(20.0));
+        assert!(capped.check(&over_cap, &[withdraw(20.0)], today)[0].refused);
+    }
+}
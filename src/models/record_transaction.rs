@@ -1,6 +1,10 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
 
+/// The number of characters shown for abbreviated record ids.
+pub const SHORT_ID_LEN: usize = 7;
+
 /// Represents a record that has been made in a pile.
 /// This is analogous to a commit in Git.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -9,12 +13,54 @@ pub struct Record {
     pub id: String,
     /// The message for this record.
     pub message: String,
-    /// The time stamp for this record. In epoch time.
-    pub time_stamp: String,
+    /// When this record was committed.
+    #[serde(deserialize_with = "crate::utils::deserialize_time_stamp")]
+    pub time_stamp: DateTime<Utc>,
     /// The amount the pile has after this record.
     pub amount_after_record: f32,
     /// The actions that have been made in this record.
     pub transactions: Vec<Transaction>,
+    /// The id of the record this one was cherry-picked from, if any.
+    #[serde(default)]
+    pub cherry_picked_from: Option<String>,
+    /// The person who made this record, if known.
+    #[serde(default)]
+    pub author: Option<Author>,
+}
+
+/// The person who made a record.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Author {
+    pub name: String,
+    pub email: Option<String>,
+}
+
+impl Author {
+    pub fn new(name: &str, email: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            email: email.map(|s| s.to_string()),
+        }
+    }
+
+    /// Whether the name or email contains the text, ignoring case.
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.name.to_lowercase().contains(&text)
+            || self
+                .email
+                .as_ref()
+                .is_some_and(|email| email.to_lowercase().contains(&text))
+    }
+}
+
+impl std::fmt::Display for Author {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.email {
+            Some(email) => write!(f, "{} <{}>", self.name, email),
+            None => write!(f, "{}", self.name),
+        }
+    }
 }
 
 /// Represents an action that has been made in a record.
@@ -28,6 +74,30 @@ pub struct Transaction {
     pub amount: f32,
     /// An optional note for this transaction   
     pub note: Option<String>,
+    /// The day the money actually moved, if it isn't the day the record was committed.
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    /// Links this transaction to the other parts of a payment split across several piles.
+    #[serde(default)]
+    pub split: Option<SplitLink>,
+}
+
+/// Identifies a payment that was split across several piles, such as one receipt covering
+/// groceries and household items. Every part carries the same link.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SplitLink {
+    pub id: String,
+    /// The amount of the whole payment.
+    pub total: f32,
+}
+
+impl SplitLink {
+    pub fn new(total: f32) -> Self {
+        Self {
+            id: nanoid!(),
+            total,
+        }
+    }
 }
 
 impl Transaction {
@@ -36,6 +106,25 @@ impl Transaction {
             transaction_type,
             amount,
             note: note.map(|s| s.to_string()),
+            date: None,
+            split: None,
+        }
+    }
+
+    pub fn with_date(self, date: Option<NaiveDate>) -> Self {
+        Self { date, ..self }
+    }
+
+    pub fn with_split(self, split: Option<SplitLink>) -> Self {
+        Self { split, ..self }
+    }
+
+    /// The amount this transaction changes a pile's balance by.
+    pub fn balance_change(&self) -> f32 {
+        match self.transaction_type {
+            TransactionType::Add => self.amount,
+            TransactionType::Withdraw => -self.amount,
+            TransactionType::Init => 0.0,
         }
     }
 }
@@ -53,7 +142,7 @@ pub enum TransactionType {
 impl Record {
     pub fn new_init(
         message: &str,
-        time_stamp: &str,
+        time_stamp: DateTime<Utc>,
         amount_after_record: f32,
         amount: Option<f32>,
     ) -> Self {
@@ -70,7 +159,7 @@ impl Record {
     }
     pub fn new(
         message: &str,
-        time_stamp: &str,
+        time_stamp: DateTime<Utc>,
         amount_after_record: f32,
         transactions: &[Transaction],
     ) -> Self {
@@ -79,7 +168,52 @@ impl Record {
             message: message.to_string(),
             amount_after_record,
             transactions: transactions.to_vec(),
-            time_stamp: time_stamp.to_string(),
+            time_stamp,
+            cherry_picked_from: None,
+            author: None,
         }
     }
+    /// The abbreviated form of this record's id, as shown in the record log.
+    pub fn short_id(&self) -> &str {
+        let end = self
+            .id
+            .char_indices()
+            .nth(SHORT_ID_LEN)
+            .map(|(index, _)| index)
+            .unwrap_or(self.id.len());
+        &self.id[..end]
+    }
+    /// The day a transaction of this record happened: its own date, or otherwise the local day
+    /// the record was committed.
+    pub fn transaction_date(&self, transaction: &Transaction) -> NaiveDate {
+        transaction
+            .date
+            .unwrap_or_else(|| self.time_stamp.with_timezone(&Local).date_naive())
+    }
+    /// The pile's balance before this record was made.
+    pub fn amount_before_record(&self) -> f32 {
+        crate::utils::round_to_two_decimals(self.amount_after_record - self.balance_change())
+    }
+    /// The amount this record changes a pile's balance by.
+    pub fn balance_change(&self) -> f32 {
+        self.transactions
+            .iter()
+            .map(|transaction| transaction.balance_change())
+            .sum()
+    }
+    pub fn set_amount_after_record(self, amount_after_record: f32) -> Self {
+        Self {
+            amount_after_record,
+            ..self
+        }
+    }
+    pub fn with_cherry_picked_from(self, source_record_id: &str) -> Self {
+        Self {
+            cherry_picked_from: Some(source_record_id.to_string()),
+            ..self
+        }
+    }
+    pub fn with_author(self, author: Option<Author>) -> Self {
+        Self { author, ..self }
+    }
 }
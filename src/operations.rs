@@ -0,0 +1,1040 @@
+//! Typed operations on the focused budget.
+//!
+//! Each operation reads what it needs through the IO traits, applies the change and returns the
+//! result. Nothing here prints, so the same operations can back the CLI or be embedded elsewhere.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use evalexpr::ContextWithMutableVariables;
+use serde::Serialize;
+
+use crate::{
+    budget_management::update_budget,
+    error::{BudgeyError, BudgeyResult},
+    file::{budget_io::BudgetIO, pile_io::PileIO},
+    hooks::{self, Hook, HookInput},
+    models::{
+        goal::{GoalProgress, SavingsGoal},
+        pile::{Pile, RecordLookupError},
+        policy::PilePolicy,
+        record_transaction::{Record, SplitLink, Transaction, TransactionType},
+    },
+    utils, BudgeyContext,
+};
+
+/// Filters for querying a pile's records.
+#[derive(Debug, Clone, Default)]
+pub struct RecordQuery {
+    /// Only include records made at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only include records made at or before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only include records whose message contains this text, ignoring case.
+    pub message_contains: Option<String>,
+    /// Only include records whose author's name or email contains this text, ignoring case.
+    pub author: Option<String>,
+    /// The maximum number of records to return.
+    pub limit: Option<usize>,
+}
+
+/// The result of cherry-picking a record onto the focused pile.
+#[derive(Debug, Clone)]
+pub struct CherryPick {
+    pub source_pile_name: String,
+    pub source_record: Record,
+    pub new_record: Record,
+    pub pile: Pile,
+}
+
+/// The result of rebasing the focused pile.
+#[derive(Debug, Clone)]
+pub struct Rebase {
+    pub pile: Pile,
+    pub replayed_records: usize,
+}
+
+/// Evaluates an amount given as a number or an arithmetic expression, such as "12.5 * 4" or
+/// "main * 0.1", using the given variables.
+pub fn evaluate_amount(expression: &str, variables: &BTreeMap<String, f64>) -> BudgeyResult<f32> {
+    let invalid = |e: evalexpr::EvalexprError| {
+        BudgeyError::InvalidAmount(expression.to_string(), e.to_string())
+    };
+    let mut expression_context = evalexpr::HashMapContext::new();
+    for (name, value) in variables {
+        expression_context
+            .set_value(name.to_string(), evalexpr::Value::Float(*value))
+            .map_err(invalid)?;
+    }
+    let amount = evalexpr::eval_with_context(expression, &expression_context)
+        .map_err(invalid)?
+        .as_number()
+        .map_err(invalid)?;
+    Ok(utils::round_to_two_decimals(amount as f32))
+}
+
+/// The variables amounts can use: the balance of every pile of the focused budget, `last` for
+/// the balance change of the focused pile's last record and the constants from the config.
+/// Characters in pile names that can't be part of a variable name are replaced with `_`, and
+/// constants take precedence over piles of the same name.
+pub fn amount_variables(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<BTreeMap<String, f64>> {
+    let mut variables = BTreeMap::new();
+    for pile in get_piles(context, budget_io, pile_io)? {
+        variables.insert(variable_name(&pile.get_name()), pile.current_balance as f64);
+    }
+    let current_pile = pile_io.get_current_pile(&budget_io.get_current_budget(context)?)?;
+    if let Some(record) = current_pile.records.last() {
+        variables.insert("last".to_string(), record.balance_change() as f64);
+    }
+    variables.extend(context.settings().constants.clone());
+    Ok(variables)
+}
+
+fn variable_name(pile_name: &str) -> String {
+    pile_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Sets the savings goal of a pile of the focused budget, replacing any goal it had.
+pub fn set_goal(
+    context: &BudgeyContext,
+    pile_name: &str,
+    goal: &SavingsGoal,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    if goal.target <= 0.0 {
+        return Err(BudgeyError::InvalidGoal(format!(
+            "the target must be positive, not {}",
+            goal.target
+        )));
+    }
+    if goal.by <= chrono::Local::now().date_naive() {
+        return Err(BudgeyError::InvalidGoal(format!(
+            "{} isn't in the future",
+            goal.by
+        )));
+    }
+    let pile =
+        get_pile(context, Some(pile_name), budget_io, pile_io)?.with_goal(Some(goal.clone()));
+    pile_io.update_pile(&pile)?;
+    Ok(pile)
+}
+
+/// Removes the savings goal of a pile of the focused budget, returning the goal removed.
+pub fn remove_goal(
+    context: &BudgeyContext,
+    pile_name: &str,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<SavingsGoal> {
+    let pile = get_pile(context, Some(pile_name), budget_io, pile_io)?;
+    let goal = pile
+        .goal
+        .clone()
+        .ok_or_else(|| BudgeyError::NoGoal(pile_name.to_string()))?;
+    pile_io.update_pile(&pile.with_goal(None))?;
+    Ok(goal)
+}
+
+/// The progress of every pile of the focused budget that has a goal, as of today.
+pub fn goal_progress(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<GoalProgress>> {
+    let today = chrono::Local::now().date_naive();
+    Ok(get_piles(context, budget_io, pile_io)?
+        .iter()
+        .filter_map(|pile| Some(pile.goal.as_ref()?.progress(pile, today)))
+        .collect())
+}
+
+/// Gets the named pile of the focused budget, or the focused pile if no name is given.
+pub fn get_pile(
+    context: &BudgeyContext,
+    pile_name: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    match pile_name {
+        Some(name) => pile_io
+            .maybe_get_pile(name, &current_budget)?
+            .ok_or_else(|| BudgeyError::PileNotFound(name.to_string())),
+        None => Ok(pile_io.get_current_pile(&current_budget)?),
+    }
+}
+
+/// Gets every pile of the focused budget.
+pub fn get_piles(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<Pile>> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let piles = current_budget
+        .pile_names
+        .iter()
+        .map(|name| pile_io.get_pile(name))
+        .collect::<anyhow::Result<Vec<Pile>>>()?;
+    Ok(piles)
+}
+
+/// Gets every pile of the focused budget, with the focused pile first.
+pub fn get_piles_focused_first(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<Pile>> {
+    let current_pile_name = budget_io.get_current_budget(context)?.current_pile_name;
+    let mut piles = get_piles(context, budget_io, pile_io)?;
+    piles.sort_by_key(|pile| pile.get_name() != current_pile_name);
+    Ok(piles)
+}
+
+/// Reads the focused pile, applies the action to it and writes the result back.
+pub fn update_pile_with_action(
+    context: &BudgeyContext,
+    action: impl FnOnce(Pile) -> BudgeyResult<Pile>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let current_pile = pile_io.get_current_pile(&current_budget)?;
+    let new_pile = action(current_pile)?;
+    pile_io.update_pile(&new_pile)?;
+    Ok(new_pile)
+}
+
+/// Stages a transaction on the focused pile, once the pile's policy and the pre-add or
+/// pre-withdraw hook allow it. `force` stages it even if the policy refuses it.
+pub fn stage_transaction(
+    context: &BudgeyContext,
+    transaction: &Transaction,
+    force: bool,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    update_pile_with_action(
+        context,
+        |pile| stage_with_hook(context, pile, transaction, force),
+        budget_io,
+        pile_io,
+    )
+}
+
+/// Splits a transaction across several piles, staging one linked part on each of them.
+///
+/// Each part is given as `<pile>=<amount>`, and one part may be `<pile>=rest` to take what the
+/// others leave. The parts must add up to the transaction's amount. Nothing is staged unless
+/// every part is valid and allowed by the piles' policies and the hooks.
+pub fn stage_split_transaction(
+    context: &BudgeyContext,
+    transaction: &Transaction,
+    parts: &[String],
+    variables: &BTreeMap<String, f64>,
+    force: bool,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<(Pile, Transaction)>> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let link = SplitLink::new(transaction.amount);
+    let mut staged = vec![];
+    for (pile_name, amount) in split_amount(transaction.amount, parts, variables)? {
+        let pile = pile_io
+            .maybe_get_pile(&pile_name, &current_budget)?
+            .ok_or_else(|| BudgeyError::PileNotFound(pile_name.to_string()))?;
+        let part = Transaction {
+            amount,
+            ..transaction.clone()
+        }
+        .with_split(Some(link.clone()));
+        staged.push((stage_with_hook(context, pile, &part, force)?, part));
+    }
+    for (pile, _) in &staged {
+        pile_io.update_pile(pile)?;
+    }
+    Ok(staged)
+}
+
+/// Works out the amount of each part of a split, in the order given.
+pub fn split_amount(
+    total: f32,
+    parts: &[String],
+    variables: &BTreeMap<String, f64>,
+) -> BudgeyResult<Vec<(String, f32)>> {
+    let invalid = |message: String| BudgeyError::InvalidSplit(message);
+    let mut amounts = vec![];
+    let mut rest_pile = None;
+    for part in parts {
+        let (pile_name, amount) = part
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("{:?} should be <pile>=<amount>", part)))?;
+        if amounts
+            .iter()
+            .any(|(name, _): &(String, Option<f32>)| name == pile_name)
+        {
+            return Err(invalid(format!("{} is named more than once", pile_name)));
+        }
+        let amount = if amount.trim() == "rest" {
+            if rest_pile.replace(pile_name).is_some() {
+                return Err(invalid("only one part can be the rest".to_string()));
+            }
+            None
+        } else {
+            let amount = evaluate_amount(amount, variables)?;
+            if amount <= 0.0 {
+                return Err(invalid(format!(
+                    "the part for {} must be positive",
+                    pile_name
+                )));
+            }
+            Some(amount)
+        };
+        amounts.push((pile_name.to_string(), amount));
+    }
+
+    let given =
+        utils::round_to_two_decimals(amounts.iter().filter_map(|(_, amount)| *amount).sum());
+    let rest = utils::round_to_two_decimals(total - given);
+    match rest_pile {
+        None if rest != 0.0 => Err(invalid(format!(
+            "the parts add up to {} but the amount is {}",
+            given, total
+        ))),
+        Some(pile_name) if rest <= 0.0 => Err(invalid(format!(
+            "the other parts add up to {}, leaving nothing for {}",
+            given, pile_name
+        ))),
+        _ => Ok(amounts
+            .into_iter()
+            .map(|(pile_name, amount)| (pile_name, amount.unwrap_or(rest)))
+            .collect()),
+    }
+}
+
+/// A part of a split payment, with the record it was committed in, if it has been.
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitPart {
+    pub pile_name: String,
+    pub record_id: Option<String>,
+    pub transaction: Transaction,
+}
+
+/// Finds every part of a split payment in the given piles, committed or staged.
+pub fn find_split_parts(piles: &[Pile], split_id: &str) -> Vec<SplitPart> {
+    let is_part = |transaction: &&Transaction| {
+        transaction
+            .split
+            .as_ref()
+            .is_some_and(|split| split.id == split_id)
+    };
+    let mut parts = vec![];
+    for pile in piles {
+        let committed = pile.records.iter().flat_map(|record| {
+            record
+                .transactions
+                .iter()
+                .filter(is_part)
+                .map(|transaction| (Some(record.id.clone()), transaction))
+        });
+        let staged = pile
+            .current_staged_transactions
+            .iter()
+            .filter(is_part)
+            .map(|transaction| (None, transaction));
+        for (record_id, transaction) in committed.chain(staged) {
+            // Piles forked from each other share records, so a part can be found more than once.
+            if !parts
+                .iter()
+                .any(|part: &SplitPart| part.record_id.is_some() && part.record_id == record_id)
+            {
+                parts.push(SplitPart {
+                    pile_name: pile.get_name(),
+                    record_id,
+                    transaction: transaction.clone(),
+                });
+            }
+        }
+    }
+    parts
+}
+
+/// Adds the transaction to the pile, once the pile's policy and the pre-add or pre-withdraw hook
+/// allow it.
+pub(crate) fn stage_with_hook(
+    context: &BudgeyContext,
+    pile: Pile,
+    transaction: &Transaction,
+    force: bool,
+) -> BudgeyResult<Pile> {
+    let new_pile = pile.add_transaction(transaction);
+    enforce_policy(&new_pile, std::slice::from_ref(transaction), force)?;
+    let hook = match transaction.transaction_type {
+        TransactionType::Add => Some(Hook::PreAdd),
+        TransactionType::Withdraw => Some(Hook::PreWithdraw),
+        TransactionType::Init => None,
+    };
+    if let Some(hook) = hook {
+        let input = HookInput::new(&new_pile).with_transaction(transaction);
+        hooks::run_hook(context, hook, input)?;
+    }
+    Ok(new_pile)
+}
+
+/// Checks transactions staged on the pile against its policy, refusing them if the policy does
+/// unless `force` is given.
+fn enforce_policy(pile: &Pile, transactions: &[Transaction], force: bool) -> BudgeyResult<()> {
+    let today = chrono::Local::now().date_naive();
+    match pile
+        .policy
+        .check(pile, transactions, today)
+        .into_iter()
+        .find(|violation| violation.refused)
+    {
+        Some(violation) if !force => Err(BudgeyError::PolicyViolation(violation.message)),
+        _ => Ok(()),
+    }
+}
+
+/// Commits the focused pile's staged transactions to a new record, once the pile's policy allows
+/// them, running the pre-commit hook before and the post-commit hook after. `force` commits them
+/// even if the policy refuses them.
+pub fn commit(
+    context: &BudgeyContext,
+    message: &str,
+    force: bool,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Record> {
+    let new_pile = update_pile_with_action(
+        context,
+        |current_pile| {
+            if current_pile.current_staged_transactions.is_empty() {
+                return Err(BudgeyError::NoStagedTransactions);
+            }
+            enforce_policy(
+                &current_pile,
+                &current_pile.current_staged_transactions,
+                force,
+            )?;
+            let new_record = Record::new(
+                message,
+                Utc::now(),
+                current_pile.current_balance,
+                &current_pile.current_staged_transactions,
+            )
+            .with_author(context.settings().author());
+            let new_pile = current_pile
+                .add_record(&new_record)
+                .clear_staged_transactions();
+            let input = HookInput::new(&new_pile).with_record(&new_record);
+            hooks::run_hook(context, Hook::PreCommit, input)?;
+            Ok(new_pile)
+        },
+        budget_io,
+        pile_io,
+    )?;
+    let new_record = new_pile
+        .records
+        .last()
+        .cloned()
+        .expect("Committed pile should have a record");
+    let input = HookInput::new(&new_pile).with_record(&new_record);
+    hooks::run_hook(context, Hook::PostCommit, input)?;
+    Ok(new_record)
+}
+
+/// Discards the focused pile's staged transactions, restoring the balance of its last record.
+pub fn restore(
+    context: &BudgeyContext,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    update_pile_with_action(
+        context,
+        |pile| {
+            let new_balance = pile.last_committed_balance();
+            Ok(pile.set_balance(new_balance).clear_staged_transactions())
+        },
+        budget_io,
+        pile_io,
+    )
+}
+
+/// Creates a new pile from the history of the source pile, or the focused pile if no source is
+/// given, and focuses it.
+pub fn create_pile(
+    context: &BudgeyContext,
+    new_pile_name: &str,
+    source: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    if current_budget
+        .pile_names
+        .iter()
+        .any(|name| name == new_pile_name)
+    {
+        return Err(BudgeyError::PileAlreadyExists(new_pile_name.to_string()));
+    }
+    let source_pile = get_pile(context, source, budget_io, pile_io)?;
+
+    let new_pile = Pile::new_user_created(
+        source_pile.current_balance,
+        new_pile_name,
+        &source_pile.records,
+    );
+    if let Err(e) = pile_io.create_new_pile(&new_pile) {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            return Err(BudgeyError::PileAlreadyExists(new_pile_name.to_string()));
+        }
+        return Err(e.into());
+    }
+    let budget = current_budget
+        .add_pile(new_pile_name)
+        .change_current_pile(new_pile_name);
+    update_budget(&context.get_current_budget_path(), &budget)?;
+    Ok(new_pile)
+}
+
+/// Deletes a pile from the focused budget, once the pre-pile-delete hook allows it, along with
+/// the tags of records no other pile has. Returns the names of the tags that were removed.
+/// The main pile can't be deleted.
+pub fn delete_pile(
+    context: &BudgeyContext,
+    pile_name: &str,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<String>> {
+    if pile_name.to_lowercase() == "main" {
+        return Err(BudgeyError::CannotDeleteMainPile);
+    }
+    let current_budget = budget_io.get_current_budget(context)?;
+    if !current_budget
+        .pile_names
+        .iter()
+        .any(|name| name == pile_name)
+    {
+        return Err(BudgeyError::PileNotFound(pile_name.to_string()));
+    }
+    let pile = pile_io.get_pile(pile_name)?;
+    hooks::run_hook(context, Hook::PrePileDelete, HookInput::new(&pile))?;
+    let remaining_piles = current_budget
+        .pile_names
+        .iter()
+        .filter(|name| *name != pile_name)
+        .map(|name| pile_io.get_pile(name))
+        .collect::<anyhow::Result<Vec<Pile>>>()?;
+    let removed_tags = current_budget
+        .tags
+        .iter()
+        .filter(|(_, record_id)| {
+            !remaining_piles
+                .iter()
+                .any(|pile| pile.find_record(record_id).is_some())
+        })
+        .map(|(tag_name, _)| tag_name.to_string())
+        .collect::<Vec<String>>();
+    let new_budget = removed_tags
+        .iter()
+        .fold(current_budget, |budget, tag_name| {
+            budget.delete_tag(tag_name)
+        });
+    pile_io.delete_pile(pile_name, &new_budget)?;
+    Ok(removed_tags)
+}
+
+/// Focuses a pile of the current budget, returning it.
+pub fn focus_pile(
+    context: &BudgeyContext,
+    pile_name: &str,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    if !current_budget
+        .pile_names
+        .iter()
+        .any(|name| name == pile_name)
+    {
+        return Err(BudgeyError::PileNotFound(pile_name.to_string()));
+    }
+    let new_budget = current_budget.change_current_pile(pile_name);
+    update_budget(&context.get_current_budget_path(), &new_budget)?;
+    Ok(pile_io.get_current_pile(&new_budget)?)
+}
+
+/// Sets the policy of the named pile, or the focused pile if no name is given, returning the
+/// pile.
+pub fn set_policy(
+    context: &BudgeyContext,
+    pile_name: Option<&str>,
+    policy: &PilePolicy,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Pile> {
+    let limits = [
+        ("limit per withdrawal", policy.max_withdrawal),
+        ("spending cap", policy.spending_cap.map(|cap| cap.amount)),
+    ];
+    for (name, amount) in limits {
+        if let Some(amount) = amount.filter(|amount| !(*amount > 0.0 && amount.is_finite())) {
+            return Err(BudgeyError::InvalidPolicy(format!(
+                "the {} must be positive, not {}",
+                name, amount
+            )));
+        }
+    }
+    let pile = get_pile(context, pile_name, budget_io, pile_io)?.with_policy(policy.clone());
+    pile_io.update_pile(&pile)?;
+    Ok(pile)
+}
+
+/// Finds a record in the named pile. If no name is given, the focused pile is searched first and
+/// then every other pile, since tags are budget-wide.
+/// The reference can be a tag, a record id or any unique prefix of one.
+pub fn find_record(
+    context: &BudgeyContext,
+    pile_name: Option<&str>,
+    reference: &str,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Record> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let reference = current_budget.resolve_reference(reference);
+    if pile_name.is_some() {
+        let pile = get_pile(context, pile_name, budget_io, pile_io)?;
+        return Ok(pile.resolve_record(reference)?.clone());
+    }
+    for pile in get_piles_focused_first(context, budget_io, pile_io)? {
+        match pile.resolve_record(reference) {
+            Ok(record) => return Ok(record.clone()),
+            Err(RecordLookupError::NotFound(_)) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(RecordLookupError::NotFound(reference.to_string()).into())
+}
+
+/// Tags a record of the focused pile, or its last record if no reference is given, returning the
+/// record.
+pub fn add_tag(
+    context: &BudgeyContext,
+    tag_name: &str,
+    reference: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Record> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    if current_budget.tags.contains_key(tag_name) {
+        return Err(BudgeyError::TagAlreadyExists(tag_name.to_string()));
+    }
+    let current_pile = pile_io.get_current_pile(&current_budget)?;
+    let record = match reference {
+        Some(reference) => {
+            current_pile.resolve_record(current_budget.resolve_reference(reference))?
+        }
+        None => current_pile
+            .records
+            .last()
+            .ok_or(BudgeyError::NoRecordsToTag)?,
+    };
+    update_budget(
+        &context.get_current_budget_path(),
+        &current_budget.add_tag(tag_name, &record.id),
+    )?;
+    Ok(record.clone())
+}
+
+/// Removes a tag from the focused budget, returning the id of the record it pointed at.
+pub fn delete_tag(
+    context: &BudgeyContext,
+    tag_name: &str,
+    budget_io: &impl BudgetIO,
+) -> BudgeyResult<String> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let record_id = current_budget
+        .tags
+        .get(tag_name)
+        .cloned()
+        .ok_or_else(|| BudgeyError::TagNotFound(tag_name.to_string()))?;
+    update_budget(
+        &context.get_current_budget_path(),
+        &current_budget.delete_tag(tag_name),
+    )?;
+    Ok(record_id)
+}
+
+/// Gets the records of a pile matching the query, newest first.
+pub fn query_records<'a>(pile: &'a Pile, query: &RecordQuery) -> Vec<&'a Record> {
+    let message_contains = query
+        .message_contains
+        .as_ref()
+        .map(|text| text.to_lowercase());
+    pile.records
+        .iter()
+        .rev()
+        .filter(|record| {
+            query.since.is_none_or(|since| record.time_stamp >= since)
+                && query.until.is_none_or(|until| record.time_stamp <= until)
+                && message_contains
+                    .as_ref()
+                    .is_none_or(|text| record.message.to_lowercase().contains(text))
+                && query.author.as_ref().is_none_or(|text| {
+                    record
+                        .author
+                        .as_ref()
+                        .is_some_and(|author| author.matches(text))
+                })
+        })
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Copies a record's transactions from another pile onto the focused pile as a new record.
+/// If no source pile is given, every other pile of the budget is searched.
+pub fn cherry_pick(
+    context: &BudgeyContext,
+    reference: &str,
+    from: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<CherryPick> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    let current_pile = pile_io.get_current_pile(&current_budget)?;
+
+    if !current_pile.current_staged_transactions.is_empty() {
+        return Err(BudgeyError::UncommittedTransactions);
+    }
+
+    let source_pile_names = match from {
+        Some(name) => {
+            if !current_budget.pile_names.iter().any(|pile| pile == name) {
+                return Err(BudgeyError::PileNotFound(name.to_string()));
+            }
+            vec![name.to_string()]
+        }
+        None => current_budget
+            .pile_names
+            .iter()
+            .filter(|name| **name != current_pile.get_name())
+            .cloned()
+            .collect(),
+    };
+
+    let record_id = current_budget.resolve_reference(reference);
+    let mut found = None;
+    for pile_name in source_pile_names {
+        let pile = pile_io.get_pile(&pile_name)?;
+        match pile.resolve_record(record_id) {
+            Ok(record) => {
+                found = Some((pile_name, record.clone()));
+                break;
+            }
+            Err(e @ RecordLookupError::Ambiguous(_)) => return Err(e.into()),
+            Err(RecordLookupError::NotFound(_)) => continue,
+        }
+    }
+
+    let Some((source_pile_name, source_record)) = found else {
+        return Err(RecordLookupError::NotFound(reference.to_string()).into());
+    };
+
+    if current_pile.records.iter().any(|record| {
+        record.id == source_record.id
+            || record.cherry_picked_from.as_deref() == Some(&source_record.id)
+    }) {
+        return Err(BudgeyError::AlreadyInHistory(
+            source_record.short_id().to_string(),
+        ));
+    }
+
+    let new_pile = cherry_pick_record(current_pile, &source_record, Utc::now());
+    pile_io.update_pile(&new_pile)?;
+
+    let new_record = new_pile
+        .records
+        .last()
+        .cloned()
+        .expect("Cherry-picked pile should have a record");
+    Ok(CherryPick {
+        source_pile_name,
+        source_record,
+        new_record,
+        pile: new_pile,
+    })
+}
+
+/// Applies the transactions of the source record to the pile and commits them as a new record.
+fn cherry_pick_record(pile: Pile, source_record: &Record, time_stamp: DateTime<Utc>) -> Pile {
+    let transactions = source_record
+        .transactions
+        .iter()
+        .filter(|transaction| transaction.transaction_type != TransactionType::Init)
+        // The copy isn't a part of the source's split payment, so it isn't linked to it.
+        .map(|transaction| transaction.clone().with_split(None))
+        .collect::<Vec<_>>();
+
+    let staged_pile = transactions
+        .iter()
+        .fold(pile, |pile, transaction| pile.add_transaction(transaction));
+
+    let new_record = Record::new(
+        &format!("Cherry-pick: {}", source_record.message),
+        time_stamp,
+        staged_pile.current_balance,
+        &transactions,
+    )
+    .with_cherry_picked_from(&source_record.id)
+    .with_author(source_record.author.clone());
+
+    staged_pile
+        .add_record(&new_record)
+        .clear_staged_transactions()
+}
+
+/// Replays the focused pile's own records on top of another pile's latest history.
+pub fn rebase_pile(
+    context: &BudgeyContext,
+    onto: &str,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Rebase> {
+    let current_budget = budget_io.get_current_budget(context)?;
+    if onto == current_budget.current_pile_name {
+        return Err(BudgeyError::RebaseOntoSelf);
+    }
+    let onto_pile = pile_io
+        .maybe_get_pile(onto, &current_budget)?
+        .ok_or_else(|| BudgeyError::PileNotFound(onto.to_string()))?;
+    let current_pile = pile_io.get_current_pile(&current_budget)?;
+    let replayed_records = current_pile.records.len() - current_pile.shared_history_len(&onto_pile);
+    let new_pile = current_pile.rebase_onto(&onto_pile);
+    pile_io.update_pile(&new_pile)?;
+    Ok(Rebase {
+        pile: new_pile,
+        replayed_records,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestBudgey;
+
+    fn parts(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|part| part.to_string()).collect()
+    }
+
+    #[test]
+    fn test_evaluate_amount_with_variables() {
+        let variables = BTreeMap::from([("main".to_string(), 250.0), ("rent".to_string(), 1200.0)]);
+        assert_eq!(25.0, evaluate_amount("main * 0.1", &variables).unwrap());
+        assert_eq!(600.0, evaluate_amount("rent / 2", &variables).unwrap());
+        assert_eq!(3.5, evaluate_amount("7 / 2.0", &variables).unwrap());
+        assert!(evaluate_amount("savings * 0.1", &variables).is_err());
+        assert_eq!("side_fund", variable_name("side-fund"));
+    }
+
+    #[test]
+    fn test_split_amount() {
+        let no_variables = BTreeMap::new();
+        let split = split_amount(
+            84.2,
+            &parts(&["groceries=50", "household=20", "gifts=rest"]),
+            &no_variables,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                ("groceries".to_string(), 50.0),
+                ("household".to_string(), 20.0),
+                ("gifts".to_string(), 14.2),
+            ],
+            split
+        );
+
+        let split = |total, given: &[&str]| split_amount(total, &parts(given), &no_variables);
+        assert!(split(84.2, &["groceries=50", "household=20"]).is_err());
+        assert!(split(60.0, &["groceries=60", "gifts=rest"]).is_err());
+        assert!(split(60.0, &["groceries=rest", "gifts=rest"]).is_err());
+        assert!(split(60.0, &["groceries=30", "groceries=30"]).is_err());
+        assert!(split(60.0, &["groceries"]).is_err());
+    }
+
+    #[test]
+    fn test_delete_pile_removes_tags_of_its_own_records() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        create_pile(context, "side", None, budget_io, pile_io).unwrap();
+        let deposit = Transaction::new(TransactionType::Add, 20.0, None);
+        stage_transaction(context, &deposit, false, budget_io, pile_io).unwrap();
+        let record = commit(context, "gift", false, budget_io, pile_io).unwrap();
+        let initialised = get_pile(context, Some("main"), budget_io, pile_io).unwrap();
+        add_tag(context, "gift", Some(&record.id), budget_io, pile_io).unwrap();
+        add_tag(
+            context,
+            "start",
+            Some(&initialised.records[0].id),
+            budget_io,
+            pile_io,
+        )
+        .unwrap();
+
+        let removed_tags = delete_pile(context, "side", budget_io, pile_io).unwrap();
+        assert_eq!(vec!["gift"], removed_tags);
+        let tags = budget_io.get_current_budget(context).unwrap().tags;
+        assert_eq!(vec!["start"], tags.into_keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_add_and_delete_tags() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        let initialised = add_tag(context, "start", None, budget_io, pile_io).unwrap();
+        assert!(matches!(
+            add_tag(context, "start", None, budget_io, pile_io),
+            Err(BudgeyError::TagAlreadyExists(_))
+        ));
+        let tagged = add_tag(context, "again", Some("start"), budget_io, pile_io).unwrap();
+        assert_eq!(initialised.id, tagged.id);
+        assert!(matches!(
+            add_tag(context, "missing", Some("nothing"), budget_io, pile_io),
+            Err(BudgeyError::RecordLookup(RecordLookupError::NotFound(_)))
+        ));
+
+        assert_eq!(
+            initialised.id,
+            delete_tag(context, "start", budget_io).unwrap()
+        );
+        assert!(matches!(
+            delete_tag(context, "start", budget_io),
+            Err(BudgeyError::TagNotFound(_))
+        ));
+        let tags = budget_io.get_current_budget(context).unwrap().tags;
+        assert_eq!(vec!["again"], tags.into_keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_find_record_resolves_tags_on_other_piles() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        create_pile(context, "side", None, budget_io, pile_io).unwrap();
+        let deposit = Transaction::new(TransactionType::Add, 20.0, None);
+        stage_transaction(context, &deposit, false, budget_io, pile_io).unwrap();
+        let record = commit(context, "gift", false, budget_io, pile_io).unwrap();
+        add_tag(context, "gift", None, budget_io, pile_io).unwrap();
+        focus_pile(context, "main", budget_io, pile_io).unwrap();
+
+        let found = find_record(context, None, "gift", budget_io, pile_io).unwrap();
+        assert_eq!(record.id, found.id);
+        assert!(matches!(
+            find_record(context, Some("main"), "gift", budget_io, pile_io),
+            Err(BudgeyError::RecordLookup(RecordLookupError::NotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn test_cherry_picked_split_parts_arent_linked_to_the_split() {
+        let link = SplitLink::new(30.0);
+        let part =
+            Transaction::new(TransactionType::Withdraw, 20.0, None).with_split(Some(link.clone()));
+        let main =
+            crate::test_utils::commit(Pile::default_main_pile(), "receipt", part, Utc::now());
+        let side = Pile::new_user_created(0.0, "side", &Pile::default_main_pile().records);
+
+        let side = cherry_pick_record(side, main.records.last().unwrap(), Utc::now());
+        let picked = side.records.last().unwrap();
+        assert_eq!(-20.0, picked.balance_change());
+        assert!(picked.transactions[0].split.is_none());
+        let parts = find_split_parts(&[main, side], &link.id);
+        assert_eq!(1, parts.len());
+        assert_eq!("main", parts[0].pile_name);
+    }
+
+    #[cfg(unix)]
+    fn write_hook(context: &BudgeyContext, hook: Hook, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let hooks_path = hooks::hooks_path(context);
+        std::fs::create_dir_all(&hooks_path).unwrap();
+        let path = utils::concat_paths(&hooks_path, hook.name());
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pre_commit_hook_vetoes_commit() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        let deposit = Transaction::new(TransactionType::Add, 20.0, None);
+        stage_transaction(context, &deposit, false, budget_io, pile_io).unwrap();
+        // Hooks run in the budget's folder, so the file is written there.
+        write_hook(
+            context,
+            Hook::PreCommit,
+            "echo not today >&2\ntouch vetoed\nexit 1",
+        );
+
+        match commit(context, "gift", false, budget_io, pile_io) {
+            Err(BudgeyError::HookRejected(hook, status)) => {
+                assert_eq!("pre-commit", hook);
+                assert_eq!("exit status: 1", status);
+            }
+            other => panic!("expected the hook to veto the commit, got {other:?}"),
+        }
+        let pile = get_pile(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(1, pile.records.len());
+        assert_eq!(1, pile.current_staged_transactions.len());
+        assert!(std::path::Path::new(&context.get_current_budget_path())
+            .join("vetoed")
+            .exists());
+
+        let order_path = budgey.beside("order");
+        let log_name = |name: &str| format!("echo {name} >> {}", order_path.display());
+        write_hook(context, Hook::PreCommit, &log_name("pre-commit"));
+        write_hook(
+            context,
+            Hook::PostCommit,
+            &format!("{}\nexit 1", log_name("post-commit")),
+        );
+        commit(context, "gift", false, budget_io, pile_io).unwrap();
+        assert_eq!(
+            "pre-commit\npost-commit\n",
+            std::fs::read_to_string(&order_path).unwrap()
+        );
+        let pile = get_pile(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(2, pile.records.len());
+        assert!(pile.current_staged_transactions.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use std::{cell::RefCell, fmt::Display};
+
+use budgey::{
+    config::Settings,
+    error::{BudgeyError, BudgeyResult},
+};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+
+/// The format command output is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable, coloured text.
+    Text,
+    /// A single JSON document per command, for scripts.
+    Json,
+}
+
+/// Collects the output of a command and writes it in the chosen format.
+///
+/// In text mode lines are printed as they are produced. In JSON mode they are dropped, and the
+/// data and error reported by the command are written as one document when it finishes.
+pub struct Output {
+    format: OutputFormat,
+    settings: Settings,
+    data: RefCell<Option<serde_json::Value>>,
+    error: RefCell<Option<String>>,
+}
+
+/// The document written for a command in JSON mode.
+#[derive(Serialize)]
+struct JsonDocument {
+    ok: bool,
+    data: Option<serde_json::Value>,
+    error: Option<JsonError>,
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    /// "usage" for problems with the command given, "internal" for failures while running it.
+    kind: &'static str,
+    message: String,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, settings: &Settings) -> Self {
+        if format == OutputFormat::Json || !settings.core.color {
+            colored::control::set_override(false);
+        }
+        Self {
+            format,
+            settings: settings.clone(),
+            data: RefCell::new(None),
+            error: RefCell::new(None),
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+
+    /// Shows an amount with the configured currency.
+    pub fn amount(&self, amount: f32) -> String {
+        self.settings.format_amount(amount)
+    }
+
+    /// Shows a time stamp in local time in the configured date format.
+    pub fn timestamp(&self, time_stamp: &DateTime<Utc>) -> String {
+        self.settings.format_timestamp(time_stamp)
+    }
+
+    /// Prints a line of human readable output. Ignored in JSON mode.
+    pub fn println(&self, line: impl Display) {
+        if self.is_text() {
+            println!("{}", line);
+        }
+    }
+
+    /// Reports something that doesn't stop the command, such as an ignored config key.
+    /// Warnings go to stderr, so they don't mix with the JSON document.
+    pub fn warn(&self, message: impl Display) {
+        eprintln!("{} {}", "warning:".yellow(), message);
+    }
+
+    /// Reports a problem with the command given, such as an unknown pile name.
+    pub fn fail(&self, message: impl Display) {
+        if self.is_text() {
+            println!("{}", message);
+        } else {
+            *self.error.borrow_mut() = Some(message.to_string());
+        }
+    }
+
+    /// Unwraps the result of an operation. Usage errors are reported with [`Output::fail`] and
+    /// give `None`, any other error is returned.
+    pub fn check<T>(&self, result: BudgeyResult<T>) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.is_usage() => {
+                self.fail(e);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Sets the data returned by the command in JSON mode.
+    pub fn data(&self, data: impl Serialize) -> anyhow::Result<()> {
+        if !self.is_text() {
+            *self.data.borrow_mut() = Some(serde_json::to_value(data)?);
+        }
+        Ok(())
+    }
+
+    /// Finishes the command, writing the JSON document if in JSON mode.
+    /// Exits with a non-zero status if the command failed in JSON mode.
+    pub fn finish(&self, result: anyhow::Result<()>) -> anyhow::Result<()> {
+        // Usage errors can also come from reading files, such as a wrong passphrase.
+        let result = match result {
+            Err(e)
+                if e.downcast_ref::<BudgeyError>()
+                    .is_some_and(BudgeyError::is_usage) =>
+            {
+                self.fail(e);
+                Ok(())
+            }
+            result => result,
+        };
+        if self.is_text() {
+            return result;
+        }
+        let error = match (self.error.take(), result) {
+            (Some(message), _) => Some(JsonError {
+                kind: "usage",
+                message,
+            }),
+            (None, Err(e)) => Some(JsonError {
+                kind: "internal",
+                message: format!("{:#}", e),
+            }),
+            (None, Ok(())) => None,
+        };
+        let document = JsonDocument {
+            ok: error.is_none(),
+            data: if error.is_none() {
+                self.data.take()
+            } else {
+                None
+            },
+            error,
+        };
+        println!("{}", serde_json::to_string_pretty(&document)?);
+        if !document.ok {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+//! Remotes: other budgey folders that budgets are pushed to, pulled from and cloned from.
+//!
+//! A remote is a path to a budgey folder laid out like `~/.budgey`, such as a folder on a shared
+//! drive. Records are exchanged per pile. A pile is fast-forwarded when one side has every record
+//! of the other plus some more, and left alone when both sides have records the other lacks.
+//!
+//! Encrypted budgets can't be exchanged with remotes, since piles are rewritten record by record
+//! on the other side. They can be copied with bundles instead.
+
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    budget_management::update_budget,
+    budgey_state::{write_budgey_state, BudgeyState},
+    encryption,
+    error::{BudgeyError, BudgeyResult},
+    file::{
+        budget_io::{BudgetIO, BudgetIOImpl},
+        pile_io::{PileIO, PileIOImpl},
+        state_io::{StateIO, StateIOImpl},
+    },
+    models::{
+        budget::Budget,
+        pile::{HistoryComparison, Pile},
+    },
+    operations, BudgeyConfig, BudgeyContext,
+};
+
+/// The name given to the remote a budget was cloned from.
+pub const DEFAULT_REMOTE_NAME: &str = "origin";
+
+/// What happened to a pile when records were sent to the other side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum PileSync {
+    /// The other side didn't have the pile, so it was created.
+    Created,
+    /// The other side was behind and took this many records.
+    FastForwarded { records: usize },
+    /// Both sides have the same records.
+    UpToDate,
+    /// The other side has this many records this side doesn't, so there was nothing to send.
+    Behind { records: usize },
+    /// Both sides have records the other doesn't, so neither was changed.
+    Diverged,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PileSyncResult {
+    pub pile_name: String,
+    #[serde(flatten)]
+    pub sync: PileSync,
+}
+
+/// Adds a remote to the focused budget, returning the absolute path it was saved with.
+pub fn add_remote(
+    context: &BudgeyContext,
+    remote_name: &str,
+    path: &str,
+    budget_io: &impl BudgetIO,
+) -> BudgeyResult<String> {
+    let budget = budget_io.get_current_budget(context)?;
+    if budget.remotes.contains_key(remote_name) {
+        return Err(BudgeyError::RemoteAlreadyExists(remote_name.to_string()));
+    }
+    let path = std::path::absolute(path)?.to_string_lossy().to_string();
+    update_budget(
+        &context.get_current_budget_path(),
+        &budget.add_remote(remote_name, &path),
+    )?;
+    Ok(path)
+}
+
+/// Removes a remote from the focused budget. Nothing is deleted from the remote itself.
+pub fn remove_remote(
+    context: &BudgeyContext,
+    remote_name: &str,
+    budget_io: &impl BudgetIO,
+) -> BudgeyResult<()> {
+    let budget = budget_io.get_current_budget(context)?;
+    if !budget.remotes.contains_key(remote_name) {
+        return Err(BudgeyError::RemoteNotFound(remote_name.to_string()));
+    }
+    update_budget(
+        &context.get_current_budget_path(),
+        &budget.remove_remote(remote_name),
+    )?;
+    Ok(())
+}
+
+/// Sends the focused budget's records to a remote, creating the budget there if needed.
+///
+/// If no remote is named, `origin` is used, or the only remote if there is just one.
+pub fn push(
+    context: &BudgeyContext,
+    remote_name: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<PileSyncResult>> {
+    refuse_encrypted(context)?;
+    let budget = budget_io.get_current_budget(context)?;
+    let remote_path = resolve_remote(&budget, remote_name)?;
+    let remote_context = remote_context(context.config(), &remote_path, &budget);
+    refuse_encrypted(&remote_context)?;
+    let remote_budget_io = BudgetIOImpl::new(remote_context.config());
+    let remote_pile_io = PileIOImpl::new(&remote_context);
+
+    let remote_budget = if Path::new(&remote_context.get_current_budget_json_path()).exists() {
+        remote_budget_io.get_current_budget(&remote_context)?
+    } else {
+        create_remote_budget(&remote_context, &budget, &remote_budget_io)?
+    };
+
+    let piles = operations::get_piles(context, budget_io, pile_io)?;
+    let (results, remote_budget) = sync_piles(&piles, remote_budget, &remote_pile_io)?;
+    update_budget(
+        &remote_context.get_current_budget_path(),
+        &remote_budget.merge_tags(&budget.tags),
+    )?;
+    Ok(results)
+}
+
+/// Takes a remote's records into the focused budget.
+///
+/// If no remote is named, `origin` is used, or the only remote if there is just one.
+pub fn pull(
+    context: &BudgeyContext,
+    remote_name: Option<&str>,
+    budget_io: &impl BudgetIO,
+    pile_io: &impl PileIO,
+) -> BudgeyResult<Vec<PileSyncResult>> {
+    refuse_encrypted(context)?;
+    let budget = budget_io.get_current_budget(context)?;
+    let remote_path = resolve_remote(&budget, remote_name)?;
+    let remote_context = remote_context(context.config(), &remote_path, &budget);
+    if !Path::new(&remote_context.get_current_budget_json_path()).exists() {
+        return Err(BudgeyError::RemoteBudgetNotFound(
+            remote_context.get_current_budget_path(),
+        ));
+    }
+    refuse_encrypted(&remote_context)?;
+    let remote_budget_io = BudgetIOImpl::new(remote_context.config());
+    let remote_pile_io = PileIOImpl::new(&remote_context);
+    let remote_budget = remote_budget_io.get_current_budget(&remote_context)?;
+
+    let remote_piles = operations::get_piles(&remote_context, &remote_budget_io, &remote_pile_io)?;
+    let (results, budget) = sync_piles(&remote_piles, budget, pile_io)?;
+    update_budget(
+        &context.get_current_budget_path(),
+        &budget.merge_tags(&remote_budget.tags),
+    )?;
+    Ok(results)
+}
+
+/// Copies a budget from a remote budgey folder and focuses it, with the remote saved as
+/// `origin`. The budget name can be left out if the remote only has one budget.
+pub fn clone_budget(
+    config: &BudgeyConfig,
+    local_state: Option<&BudgeyState>,
+    path: &str,
+    budget_name: Option<&str>,
+) -> BudgeyResult<Budget> {
+    let path = std::path::absolute(path)?.to_string_lossy().to_string();
+    let remote_config = BudgeyConfig::new(&path, &config.state_json_name);
+    let remote_state = match StateIOImpl::new(&remote_config).read_budgey_state() {
+        Ok(state) => state,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(BudgeyError::RemoteBudgetNotFound(path));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let budget_name = match (budget_name, remote_state.budget_names.as_slice()) {
+        (Some(name), names) if names.iter().any(|budget| budget == name) => name.to_string(),
+        (Some(name), _) => {
+            return Err(BudgeyError::RemoteBudgetNotFound(
+                remote_config.get_budget_path(name),
+            ))
+        }
+        (None, [only]) => only.to_string(),
+        (None, []) => return Err(BudgeyError::RemoteBudgetNotFound(path)),
+        (None, names) => return Err(BudgeyError::AmbiguousRemoteBudget(names.join(", "))),
+    };
+    if local_state.is_some_and(|state| state.budget_names.contains(&budget_name)) {
+        return Err(BudgeyError::BudgetAlreadyExists(budget_name));
+    }
+
+    let remote_context = BudgeyContext::new(&BudgeyState::new_init(&budget_name), &remote_config);
+    refuse_encrypted(&remote_context)?;
+    let remote_budget_io = BudgetIOImpl::new(&remote_config);
+    let remote_pile_io = PileIOImpl::new(&remote_context);
+    let remote_budget = remote_budget_io.get_current_budget(&remote_context)?;
+    let remote_piles = operations::get_piles(&remote_context, &remote_budget_io, &remote_pile_io)?;
+
+    let budget = Budget::new(
+        remote_budget.budget_detail.clone(),
+        remote_budget.pile_names.clone(),
+        remote_budget.current_pile_name.clone(),
+        remote_budget.tags.clone(),
+        BTreeMap::from([(DEFAULT_REMOTE_NAME.to_string(), path)]),
+        remote_budget.allocation_rules.clone(),
+    );
+    fs::create_dir_all(&config.root_path)?;
+    BudgetIOImpl::new(config).create_new_budget(&budget)?;
+
+    let state = match local_state {
+        Some(state) => state
+            .add_budget_name(&budget_name)
+            .change_focused_budget_name(&budget_name),
+        None => BudgeyState::new_init(&budget_name),
+    };
+    write_budgey_state(config, &state)?;
+
+    let pile_io = PileIOImpl::new(&BudgeyContext::new(&state, config));
+    for pile in &remote_piles {
+        pile_io.create_new_pile(&pile.committed_copy())?;
+    }
+    Ok(budget)
+}
+
+/// Finds the path of the named remote, or of the default remote if no name is given.
+fn resolve_remote(budget: &Budget, remote_name: Option<&str>) -> BudgeyResult<String> {
+    if let Some(name) = remote_name {
+        return budget
+            .remotes
+            .get(name)
+            .cloned()
+            .ok_or_else(|| BudgeyError::RemoteNotFound(name.to_string()));
+    }
+    if let Some(path) = budget.remotes.get(DEFAULT_REMOTE_NAME) {
+        return Ok(path.to_string());
+    }
+    match budget.remotes.values().collect::<Vec<_>>().as_slice() {
+        [only] => Ok(only.to_string()),
+        _ => Err(BudgeyError::NoRemote),
+    }
+}
+
+/// A context for the same budget in the remote budgey folder.
+fn remote_context(config: &BudgeyConfig, remote_path: &str, budget: &Budget) -> BudgeyContext {
+    let budget_name = &budget.budget_detail.budget_name;
+    BudgeyContext::new(
+        &BudgeyState::new_init(budget_name),
+        &BudgeyConfig::new(remote_path, &config.state_json_name),
+    )
+}
+
+/// Fails if the context's budget is encrypted.
+fn refuse_encrypted(context: &BudgeyContext) -> BudgeyResult<()> {
+    let budget_path = context.get_current_budget_path();
+    if encryption::is_encrypted(&budget_path) {
+        return Err(BudgeyError::EncryptedRemoteBudget(budget_path));
+    }
+    Ok(())
+}
+
+/// Creates an empty copy of the budget in the remote budgey folder and lists it in the remote's
+/// state, so the folder can be cloned from.
+fn create_remote_budget(
+    remote_context: &BudgeyContext,
+    budget: &Budget,
+    remote_budget_io: &impl BudgetIO,
+) -> anyhow::Result<Budget> {
+    let remote_config = remote_context.config();
+    fs::create_dir_all(&remote_config.root_path)?;
+    let remote_budget = Budget::new(
+        budget.budget_detail.clone(),
+        vec![],
+        budget.current_pile_name.clone(),
+        BTreeMap::new(),
+        BTreeMap::new(),
+        budget.allocation_rules.clone(),
+    );
+    remote_budget_io.create_new_budget(&remote_budget)?;
+
+    let budget_name = &budget.budget_detail.budget_name;
+    let remote_state = match StateIOImpl::new(remote_config).read_budgey_state() {
+        Ok(state) if state.budget_names.contains(budget_name) => state,
+        Ok(state) => state.add_budget_name(budget_name),
+        Err(e) if e.kind() == ErrorKind::NotFound => BudgeyState::new_init(budget_name),
+        Err(e) => return Err(e.into()),
+    };
+    write_budgey_state(remote_config, &remote_state)?;
+    Ok(remote_budget)
+}
+
+/// Sends each of the source piles to the destination budget, returning what happened to each
+/// along with the destination budget, which lists any piles that were created.
+fn sync_piles(
+    source_piles: &[Pile],
+    destination_budget: Budget,
+    destination_pile_io: &impl PileIO,
+) -> anyhow::Result<(Vec<PileSyncResult>, Budget)> {
+    let mut destination_budget = destination_budget;
+    let mut results = vec![];
+    for source in source_piles {
+        let pile_name = source.get_name();
+        let sync = match destination_pile_io.maybe_get_pile(&pile_name, &destination_budget)? {
+            None => {
+                destination_pile_io.create_new_pile(&source.committed_copy())?;
+                destination_budget = destination_budget.add_pile(&pile_name);
+                PileSync::Created
+            }
+            Some(destination) => match source.compare_history(&destination) {
+                HistoryComparison::Same => PileSync::UpToDate,
+                HistoryComparison::Ahead(records) => {
+                    destination_pile_io.update_pile(&destination.fast_forward_to(source))?;
+                    PileSync::FastForwarded { records }
+                }
+                HistoryComparison::Behind(records) => PileSync::Behind { records },
+                HistoryComparison::Diverged => PileSync::Diverged,
+            },
+        };
+        results.push(PileSyncResult { pile_name, sync });
+    }
+    Ok((results, destination_budget))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::record_transaction::{Transaction, TransactionType},
+        test_utils::TestBudgey,
+    };
+
+    fn commit_deposit(context: &BudgeyContext, message: &str) {
+        let budget_io = BudgetIOImpl::new(context.config());
+        let pile_io = PileIOImpl::new(context);
+        let deposit = Transaction::new(TransactionType::Add, 10.0, None);
+        operations::stage_transaction(context, &deposit, false, &budget_io, &pile_io).unwrap();
+        operations::commit(context, message, false, &budget_io, &pile_io).unwrap();
+    }
+
+    fn main_sync(results: &[PileSyncResult]) -> PileSync {
+        results
+            .iter()
+            .find(|result| result.pile_name == "main")
+            .unwrap()
+            .sync
+    }
+
+    #[test]
+    fn test_encrypted_budgets_arent_exchanged_with_remotes() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        let remote_path = budgey.beside("remote");
+        add_remote(context, "nas", &remote_path.to_string_lossy(), budget_io).unwrap();
+        let key_file = |root: &Path| root.join("home").join(encryption::KEY_FILE_NAME);
+
+        fs::write(key_file(&budgey.root()), "{}").unwrap();
+        assert!(matches!(
+            push(context, None, budget_io, pile_io),
+            Err(BudgeyError::EncryptedRemoteBudget(_))
+        ));
+        assert!(!remote_path.exists());
+
+        fs::remove_file(key_file(&budgey.root())).unwrap();
+        push(context, None, budget_io, pile_io).unwrap();
+        fs::write(key_file(&remote_path), "{}").unwrap();
+        assert!(matches!(
+            push(context, None, budget_io, pile_io),
+            Err(BudgeyError::EncryptedRemoteBudget(_))
+        ));
+        assert!(matches!(
+            pull(context, None, budget_io, pile_io),
+            Err(BudgeyError::EncryptedRemoteBudget(_))
+        ));
+        let other = BudgeyConfig::new(
+            &budgey.beside("other").to_string_lossy(),
+            &budgey.config().state_json_name,
+        );
+        assert!(matches!(
+            clone_budget(&other, None, &remote_path.to_string_lossy(), None),
+            Err(BudgeyError::EncryptedRemoteBudget(_))
+        ));
+        assert!(!budgey.beside("other").exists());
+    }
+
+    #[test]
+    fn test_pull_fast_forwards_and_leaves_diverged_piles_alone() {
+        let budgey = TestBudgey::new("home");
+        let TestBudgey {
+            context,
+            budget_io,
+            pile_io,
+            ..
+        } = &budgey;
+        let remote_path = budgey.beside("remote").to_string_lossy().to_string();
+        add_remote(context, "origin", &remote_path, budget_io).unwrap();
+        push(context, None, budget_io, pile_io).unwrap();
+
+        let other_config = BudgeyConfig::new(
+            &budgey.beside("other").to_string_lossy(),
+            &budgey.config().state_json_name,
+        );
+        clone_budget(&other_config, None, &remote_path, None).unwrap();
+        let other = BudgeyContext::new(&BudgeyState::new_init("home"), &other_config);
+        let other_budget_io = BudgetIOImpl::new(&other_config);
+        let other_pile_io = PileIOImpl::new(&other);
+        commit_deposit(&other, "from the other machine");
+        push(&other, None, &other_budget_io, &other_pile_io).unwrap();
+
+        let pulled = pull(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(PileSync::FastForwarded { records: 1 }, main_sync(&pulled));
+        let main = operations::get_pile(context, Some("main"), budget_io, pile_io).unwrap();
+        assert_eq!(
+            "from the other machine",
+            main.records.last().unwrap().message
+        );
+
+        commit_deposit(context, "local");
+        commit_deposit(&other, "other");
+        push(&other, None, &other_budget_io, &other_pile_io).unwrap();
+        let pulled = pull(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(PileSync::Diverged, main_sync(&pulled));
+        let pushed = push(context, None, budget_io, pile_io).unwrap();
+        assert_eq!(PileSync::Diverged, main_sync(&pushed));
+        let main = operations::get_pile(context, Some("main"), budget_io, pile_io).unwrap();
+        assert_eq!("local", main.records.last().unwrap().message);
+        let remote = BudgeyContext::new(
+            &BudgeyState::new_init("home"),
+            &BudgeyConfig::new(&remote_path, &budgey.config().state_json_name),
+        );
+        let remote_main = operations::get_pile(
+            &remote,
+            Some("main"),
+            &BudgetIOImpl::new(remote.config()),
+            &PileIOImpl::new(&remote),
+        )
+        .unwrap();
+        assert_eq!("other", remote_main.records.last().unwrap().message);
+    }
+}
@@ -0,0 +1,87 @@
+//! Fixtures shared by the unit tests.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use tempfile::TempDir;
+
+use crate::{
+    budgey_state::{write_budgey_state, BudgeyState},
+    file::{
+        budget_io::{BudgetIO, BudgetIOImpl},
+        pile_io::{PileIO, PileIOImpl},
+    },
+    models::{
+        budget::Budget,
+        pile::Pile,
+        record_transaction::{Record, Transaction},
+    },
+    utils::create_json_file_name,
+    BudgeyConfig, BudgeyContext,
+};
+
+/// A budgey folder with one budget in a temporary folder, and the IO used to read and write it.
+/// Everything is removed when it is dropped.
+pub struct TestBudgey {
+    folder: TempDir,
+    pub context: BudgeyContext,
+    pub budget_io: BudgetIOImpl,
+    pub pile_io: PileIOImpl,
+}
+
+impl TestBudgey {
+    /// Initialises the budgey folder with one budget, like `budgey init`.
+    pub fn new(budget_name: &str) -> Self {
+        let folder = tempfile::tempdir().unwrap();
+        let context = init_budgey(&folder.path().join("budgey"), budget_name);
+        Self {
+            folder,
+            budget_io: BudgetIOImpl::new(context.config()),
+            pile_io: PileIOImpl::new(&context),
+            context,
+        }
+    }
+
+    pub fn config(&self) -> &BudgeyConfig {
+        self.context.config()
+    }
+
+    /// The budgey root folder.
+    pub fn root(&self) -> PathBuf {
+        self.folder.path().join("budgey")
+    }
+
+    /// A path next to the budgey root folder, such as for a remote or another root folder.
+    pub fn beside(&self, name: &str) -> PathBuf {
+        self.folder.path().join(name)
+    }
+}
+
+fn init_budgey(root: &Path, budget_name: &str) -> BudgeyContext {
+    let config = BudgeyConfig::new(
+        &root.to_string_lossy(),
+        &create_json_file_name("budgey_state"),
+    );
+    let state = BudgeyState::new_init(budget_name);
+    write_budgey_state(&config, &state).unwrap();
+    BudgetIOImpl::new(&config)
+        .create_new_budget(&Budget::new_init(budget_name))
+        .unwrap();
+    let context = BudgeyContext::new(&state, &config);
+    PileIOImpl::new(&context)
+        .create_new_pile(&Pile::default_main_pile())
+        .unwrap();
+    context
+}
+
+/// Stages the transaction on the pile and commits it to a record made at the given time.
+pub fn commit(
+    pile: Pile,
+    message: &str,
+    transaction: Transaction,
+    time_stamp: DateTime<Utc>,
+) -> Pile {
+    let pile = pile.add_transaction(&transaction);
+    let record = Record::new(message, time_stamp, pile.current_balance, &[transaction]);
+    pile.add_record(&record).clear_staged_transactions()
+}
@@ -1,3 +1,5 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+
 /// Creates a json file with the given path and name.
 /// budgey_path -> path to the budgey directory
 /// name -> name of the json file
@@ -15,12 +17,129 @@ pub fn concat_paths(root: &str, new: &str) -> String {
     format!("{}/{}", root, new)
 }
 
-pub fn get_current_timestamp() -> anyhow::Result<String> {
-    let current_time = std::time::SystemTime::now()
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)?
-        .as_secs()
-        .to_string();
-    Ok(current_time)
+/// Reads every file under the given folder, keyed by path relative to it with '/' separators.
+/// A folder that doesn't exist has no files.
+pub fn read_files_recursively(
+    directory: &std::path::Path,
+) -> std::io::Result<std::collections::BTreeMap<String, Vec<u8>>> {
+    fn read_into(
+        directory: &std::path::Path,
+        relative_path: &str,
+        files: &mut std::collections::BTreeMap<String, Vec<u8>>,
+    ) -> std::io::Result<()> {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = if relative_path.is_empty() {
+                name
+            } else {
+                concat_paths(relative_path, &name)
+            };
+            if entry.file_type()?.is_dir() {
+                read_into(&entry.path(), &path, files)?;
+            } else {
+                files.insert(path, std::fs::read(entry.path())?);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = std::collections::BTreeMap::new();
+    read_into(directory, "", &mut files)?;
+    Ok(files)
+}
+
+/// Formats a time stamp as a local date and time with the given chrono format string.
+/// Falls back to RFC 3339 if the format string is invalid.
+pub fn format_timestamp(time_stamp: &DateTime<Utc>, date_format: &str) -> String {
+    use std::fmt::Write;
+
+    let mut formatted = String::new();
+    match write!(
+        formatted,
+        "{}",
+        time_stamp.with_timezone(&Local).format(date_format)
+    ) {
+        Ok(()) => formatted,
+        Err(_) => time_stamp.to_rfc3339(),
+    }
+}
+
+/// Parses a date given by the user: `today`, `yesterday`, `<n> days ago` or YYYY-MM-DD.
+pub fn parse_date(date: &str) -> anyhow::Result<NaiveDate> {
+    let today = Local::now().date_naive();
+    let days_ago = match date.trim().to_lowercase().as_str() {
+        "today" => Some(0),
+        "yesterday" => Some(1),
+        relative => relative
+            .strip_suffix(" days ago")
+            .or_else(|| relative.strip_suffix(" day ago"))
+            .and_then(|days| days.trim().parse::<u64>().ok()),
+    };
+    if let Some(days) = days_ago {
+        return today
+            .checked_sub_days(chrono::Days::new(days))
+            .ok_or_else(|| anyhow::anyhow!("Invalid date {:?}, it is too far back", date));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| {
+        anyhow::anyhow!(
+            "Invalid date {:?}, expected YYYY-MM-DD, today, yesterday or <n> days ago: {}",
+            date,
+            e
+        )
+    })
+}
+
+/// The start of the given date in local time.
+pub fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+/// The last moment of the given date in local time.
+pub fn end_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.succ_opt()
+        .map(|next_day| start_of_day(next_day) - chrono::TimeDelta::nanoseconds(1))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Reads a time stamp written as RFC 3339, or as seconds since the epoch by older versions of
+/// budgey.
+pub fn deserialize_time_stamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::{de::Error, Deserialize};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredTimeStamp {
+        Seconds(i64),
+        Text(String),
+    }
+
+    let seconds = match StoredTimeStamp::deserialize(deserializer)? {
+        StoredTimeStamp::Seconds(seconds) => seconds,
+        StoredTimeStamp::Text(text) => match text.parse::<i64>() {
+            Ok(seconds) => seconds,
+            Err(_) => {
+                return DateTime::parse_from_rfc3339(&text)
+                    .map(|date_time| date_time.with_timezone(&Utc))
+                    .map_err(D::Error::custom)
+            }
+        },
+    };
+    DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| D::Error::custom(format!("Invalid time stamp {}", seconds)))
 }
 
 pub fn round_to_two_decimals(value: f32) -> f32 {
@@ -33,6 +152,21 @@ pub fn round_to_two_decimals(value: f32) -> f32 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_deserialize_time_stamp_reads_epoch_seconds_and_rfc_3339() {
+        #[derive(serde::Deserialize)]
+        struct Stored {
+            #[serde(deserialize_with = "deserialize_time_stamp")]
+            time_stamp: DateTime<Utc>,
+        }
+        let read = |json: &str| serde_json::from_str::<Stored>(json).unwrap().time_stamp;
+
+        let expected = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(expected, read(r#"{"time_stamp":"1700000000"}"#));
+        assert_eq!(expected, read(r#"{"time_stamp":1700000000}"#));
+        assert_eq!(expected, read(r#"{"time_stamp":"2023-11-14T22:13:20Z"}"#));
+    }
+
     #[test]
     fn test_create_json_path() {
         let budgey_path = "/tmp";